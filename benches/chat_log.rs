@@ -0,0 +1,156 @@
+//! Benchmarks polling the chat log with a fake memory source.
+//!
+//! Run with `cargo bench`. Each case reports how long reading took and how many reads of memory
+//! were made, since every read is a system call when reading from a real process.
+
+extern crate byteorder;
+extern crate ffxiv_reader;
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use ffxiv_reader::*;
+
+use std::io;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+const MODULE: &'static str = "ffxiv_dx11.exe";
+const BASE: usize = 0x1000_0000;
+const CHAIN_1: usize = 0x2000_0000;
+const CHAIN_2: usize = 0x3000_0000;
+const TABLE: usize = 0x4000_0000;
+const CHAT: usize = 0x5000_0000;
+const LINES_PER_PAGE: usize = 1000;
+
+struct ChatState {
+  /// The total number of lines written
+  count: usize,
+  /// The end of each line on the current page in the chat buffer
+  table: Vec<u8>,
+  chat: Vec<u8>,
+  polls_left: usize
+}
+
+/// A game that writes `lines_per_poll` lines before each of `polls` polls.
+struct FakeChatLog {
+  state: Mutex<ChatState>,
+  lines_per_poll: usize,
+  reads: AtomicUsize
+}
+
+impl FakeChatLog {
+  fn new(lines: usize, lines_per_poll: usize, polls: usize) -> Self {
+    let log = FakeChatLog {
+      state: Mutex::new(ChatState {
+        count: 0,
+        table: Vec::new(),
+        chat: Vec::new(),
+        polls_left: polls
+      }),
+      lines_per_poll,
+      reads: AtomicUsize::new(0)
+    };
+    log.write_lines(lines);
+    log
+  }
+
+  fn write_lines(&self, lines: usize) {
+    let mut state = self.state.lock().unwrap();
+    for _ in 0..lines {
+      if state.count % LINES_PER_PAGE == 0 {
+        state.table.clear();
+        state.chat.clear();
+      }
+      let timestamp = 1_500_000_000 + state.count as u32;
+      let mut header = [0; 8];
+      LittleEndian::write_u32(&mut header, timestamp);
+      header[4] = 0x0a;
+      state.chat.extend_from_slice(&header);
+      state.chat.extend_from_slice(b":Sender Name:");
+      state.chat.extend_from_slice(b"A line of chat that is about as long as a usual one.");
+      let mut end = [0; 4];
+      LittleEndian::write_u32(&mut end, state.chat.len() as u32);
+      state.table.extend_from_slice(&end);
+      state.count += 1;
+    }
+  }
+}
+
+fn read_region(region: &[u8], offset: usize, buf: &mut [u8]) -> io::Result<()> {
+  match region.get(offset..offset + buf.len()) {
+    Some(bytes) => {
+      buf.copy_from_slice(bytes);
+      Ok(())
+    },
+    None => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "read past the end of a region"))
+  }
+}
+
+impl MemorySource for FakeChatLog {
+  fn read_into(&self, address: usize, buf: &mut [u8]) -> io::Result<()> {
+    self.reads.fetch_add(1, Ordering::Relaxed);
+    let state = self.state.lock().unwrap();
+    let lines = CHAIN_2 + 0x2b8;
+    let pointer = match address {
+      a if a == BASE + 0x017a_4138 => CHAIN_1,
+      a if a == CHAIN_1 + 0x60 => CHAIN_2,
+      a if a == lines => state.count,
+      a if a == lines + 40 => TABLE + state.table.len(),
+      a if a == lines + 52 => CHAT,
+      a if a >= CHAT => return read_region(&state.chat, a - CHAT, buf),
+      a if a >= TABLE => return read_region(&state.table, a - TABLE, buf),
+      _ => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "unmapped address"))
+    };
+    let mut bytes = [0; 4];
+    LittleEndian::write_u32(&mut bytes, pointer as u32);
+    read_region(&bytes, 0, buf)
+  }
+
+  fn base_address(&self, module: &str) -> Option<usize> {
+    if module == MODULE {
+      Some(BASE)
+    } else {
+      None
+    }
+  }
+
+  fn next_poll(&self) {
+    let polling = {
+      let mut state = self.state.lock().unwrap();
+      let polling = state.polls_left > 0;
+      if polling {
+        state.polls_left -= 1;
+      }
+      polling
+    };
+    if polling {
+      self.write_lines(self.lines_per_poll);
+    }
+  }
+
+  fn has_pending_changes(&self) -> bool {
+    self.state.lock().unwrap().polls_left > 0
+  }
+}
+
+fn bench(name: &str, lines: usize, lines_per_poll: usize, polls: usize) {
+  let source = FakeChatLog::new(lines, lines_per_poll, polls);
+  let reader = MemoryEntryReader::with_source(source, true);
+  let started = Instant::now();
+  let rx = reader.start_raw().unwrap();
+  let messages = rx.iter().filter(|e| e.is_item()).count();
+  let elapsed = started.elapsed();
+  let micros = elapsed.as_secs() * 1_000_000 + elapsed.subsec_nanos() as u64 / 1000;
+  println!("{}: {} messages in {} µs, {} reads",
+           name,
+           messages,
+           micros,
+           reader.source().reads.load(Ordering::Relaxed));
+}
+
+fn main() {
+  bench("full page", 999, 0, 0);
+  bench("10 lines per poll", 0, 10, 99);
+  bench("100 lines per poll over page turns", 0, 100, 30);
+}
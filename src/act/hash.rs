@@ -0,0 +1,77 @@
+//! Integrity hashes of ACT network log lines
+//!
+//! ACT ends every line of a network log with a hash of the rest of the line and the line's number
+//! in the log, so lines that were edited, removed or moved after being written no longer match
+//! their hashes. Older versions of the plugin used MD5, written as 32 hexadecimal digits. Newer
+//! versions use the first 8 bytes of SHA-256, written as 16 digits.
+//!
+//! Each line is hashed on its own, with no part of the lines before it. Lines are numbered from 1
+//! at the start of each log file. When a log is truncated, or ACT moves on to a new log, numbering
+//! starts from 1 again at the start of the file being read.
+
+use md5;
+use sha2::{Digest, Sha256};
+
+/// What an [`ActReader`](../struct.ActReader.html) does with lines that don't match their hashes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashCheck {
+  /// Don't check hashes.
+  Off,
+  /// Send an `InvalidLineHash` error for the line, then the line itself.
+  Flag,
+  /// Send an `InvalidLineHash` error for the line instead of the line.
+  Reject
+}
+
+/// The hash functions used by different versions of ACT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+  /// MD5, written as 32 hexadecimal digits.
+  Md5,
+  /// The first 8 bytes of SHA-256, written as 16 hexadecimal digits.
+  Sha256
+}
+
+impl HashAlgorithm {
+  /// Guesses the hash function from the length of a hash.
+  pub fn from_hash(hash: &str) -> Option<HashAlgorithm> {
+    match hash.len() {
+      32 => Some(HashAlgorithm::Md5),
+      16 => Some(HashAlgorithm::Sha256),
+      _ => None
+    }
+  }
+}
+
+/// Computes the hash of a line.
+///
+/// `content` is the line up to and including the `|` before its hash, and `number` is the line's
+/// number in the log, counting from 1.
+pub fn line_hash(content: &str, number: u64, algorithm: HashAlgorithm) -> String {
+  let input = format!("{}{}", content, number);
+  match algorithm {
+    HashAlgorithm::Md5 => format!("{:x}", md5::compute(input.as_bytes())),
+    HashAlgorithm::Sha256 => {
+      let mut hasher = Sha256::default();
+      hasher.input(input.as_bytes());
+      hasher.result()[..8].iter().map(|b| format!("{:02x}", b)).collect()
+    }
+  }
+}
+
+/// Checks if a line matches its hash.
+///
+/// `number` is the line's number in the log, counting from 1. A trailing line break is ignored.
+/// Lines without a hash, or with a hash of an unknown length, don't match.
+pub fn verify_line(line: &str, number: u64) -> bool {
+  let line = line.lines().next().unwrap_or("");
+  let split = match line.rfind('|') {
+    Some(i) => i + 1,
+    None => return false
+  };
+  let (content, hash) = line.split_at(split);
+  match HashAlgorithm::from_hash(hash) {
+    Some(algorithm) => line_hash(content, number, algorithm).eq_ignore_ascii_case(hash),
+    None => false
+  }
+}
@@ -0,0 +1,716 @@
+//! The types of lines in an ACT network log
+//!
+//! Every line in a network log starts with a two-digit line type and a timestamp, and ends with a
+//! hash of the line. The fields in between depend on the type. Chat lines become
+//! [`Entry`](../../messages/entries/struct.Entry.html)s, the line types below get a struct of their
+//! own, and any other line is kept as its raw fields.
+//!
+//! | Type  | Variant                                    |
+//! |-------|--------------------------------------------|
+//! | 00    | `Chat`                                     |
+//! | 01    | `ZoneChange`                               |
+//! | 02    | `PrimaryPlayer`                            |
+//! | 03/04 | `AddCombatant`/`RemoveCombatant`           |
+//! | 21/22 | `Ability`/`AoeAbility`                     |
+//! | 25    | `Death`                                    |
+//! | 26/30 | `StatusGained`/`StatusLost`                |
+//! | 33    | `ActorControl`                             |
+//! | 37    | `ResourceUpdate`                           |
+//!
+//! Fields that were added to a line type in later versions of the ACT plugin are `Option`s, so
+//! older logs can still be read. A line of a known type whose fields can't be parsed is kept as a
+//! `Raw` line.
+
+use messages::entries::Entry;
+
+use chrono::DateTime;
+
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+/// A line from an ACT network log.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum ActLine {
+  /// A chat message (type 00).
+  Chat(Entry),
+  /// The player moved to a new zone (type 01).
+  ZoneChange(ZoneChange),
+  /// The player whose client wrote the log changed (type 02).
+  PrimaryPlayer(PrimaryPlayer),
+  /// An actor came into range (type 03).
+  AddCombatant(Combatant),
+  /// An actor went out of range (type 04).
+  RemoveCombatant(Combatant),
+  /// An ability used on a single target (type 21).
+  Ability(Ability),
+  /// One target hit by an ability with several targets (type 22).
+  AoeAbility(Ability),
+  /// An actor died (type 25).
+  Death(Death),
+  /// An actor gained a status effect (type 26).
+  StatusGained(StatusEffect),
+  /// An actor lost a status effect (type 30).
+  StatusLost(StatusEffect),
+  /// A control packet for an actor, such as a fade out or a limit break gauge update (type 33).
+  ActorControl(ActorControl),
+  /// An update to an actor's HP and MP (type 37).
+  ResourceUpdate(ResourceUpdate),
+  /// Any other line.
+  Raw(RawLine)
+}
+
+impl ActLine {
+  /// Parses a line from an ACT network log.
+  ///
+  /// Returns `None` if the line doesn't start with a line type and a timestamp.
+  pub fn parse(line: &str) -> Option<ActLine> {
+    let raw = opt!(RawLine::parse(line));
+    if raw.kind == 0 {
+      return Some(match super::parse_line(line) {
+        Some(entry) => ActLine::Chat(entry),
+        None => ActLine::Raw(raw)
+      });
+    }
+    let typed = {
+      let f = Fields::new(raw.timestamp, &raw.fields);
+      match raw.kind {
+        1 => ZoneChange::parse(&f).map(ActLine::ZoneChange),
+        2 => PrimaryPlayer::parse(&f).map(ActLine::PrimaryPlayer),
+        3 => Combatant::parse(&f).map(ActLine::AddCombatant),
+        4 => Combatant::parse(&f).map(ActLine::RemoveCombatant),
+        21 => Ability::parse(&f).map(ActLine::Ability),
+        22 => Ability::parse(&f).map(ActLine::AoeAbility),
+        25 => Death::parse(&f).map(ActLine::Death),
+        26 => StatusEffect::parse(&f).map(ActLine::StatusGained),
+        30 => StatusEffect::parse(&f).map(ActLine::StatusLost),
+        33 => ActorControl::parse(&f).map(ActLine::ActorControl),
+        37 => ResourceUpdate::parse(&f).map(ActLine::ResourceUpdate),
+        _ => None
+      }
+    };
+    Some(typed.unwrap_or(ActLine::Raw(raw)))
+  }
+
+  /// The line type, such as 21 for an ability.
+  pub fn kind(&self) -> u16 {
+    match *self {
+      ActLine::Chat(_) => 0,
+      ActLine::ZoneChange(_) => 1,
+      ActLine::PrimaryPlayer(_) => 2,
+      ActLine::AddCombatant(_) => 3,
+      ActLine::RemoveCombatant(_) => 4,
+      ActLine::Ability(_) => 21,
+      ActLine::AoeAbility(_) => 22,
+      ActLine::Death(_) => 25,
+      ActLine::StatusGained(_) => 26,
+      ActLine::StatusLost(_) => 30,
+      ActLine::ActorControl(_) => 33,
+      ActLine::ResourceUpdate(_) => 37,
+      ActLine::Raw(ref r) => r.kind
+    }
+  }
+
+  /// The time the line was written, in seconds since the Unix epoch.
+  pub fn timestamp(&self) -> u32 {
+    match *self {
+      ActLine::Chat(ref e) => e.timestamp,
+      ActLine::ZoneChange(ref l) => l.timestamp,
+      ActLine::PrimaryPlayer(ref l) => l.timestamp,
+      ActLine::AddCombatant(ref l) | ActLine::RemoveCombatant(ref l) => l.timestamp,
+      ActLine::Ability(ref l) | ActLine::AoeAbility(ref l) => l.timestamp,
+      ActLine::Death(ref l) => l.timestamp,
+      ActLine::StatusGained(ref l) | ActLine::StatusLost(ref l) => l.timestamp,
+      ActLine::ActorControl(ref l) => l.timestamp,
+      ActLine::ResourceUpdate(ref l) => l.timestamp,
+      ActLine::Raw(ref l) => l.timestamp
+    }
+  }
+}
+
+/// A line of a type without a struct of its own, split into its fields.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RawLine {
+  /// The line type.
+  pub kind: u16,
+  /// The time the line was written, in seconds since the Unix epoch.
+  pub timestamp: u32,
+  /// The fields between the timestamp and the hash at the end of the line.
+  pub fields: Vec<String>
+}
+
+impl RawLine {
+  /// Splits a line from an ACT network log into its fields.
+  ///
+  /// Returns `None` if the line doesn't start with a line type and a timestamp.
+  pub fn parse(line: &str) -> Option<RawLine> {
+    let mut parts = line.split('|');
+    let kind = opt!(parts.next().and_then(|x| x.parse().ok()));
+    let datetime = opt!(parts.next().and_then(|x| DateTime::parse_from_rfc3339(x).ok()));
+    let mut fields: Vec<String> = parts.map(String::from).collect();
+    // The last field is the hash of the line
+    fields.pop();
+    Some(RawLine {
+      kind,
+      timestamp: datetime.timestamp() as u32,
+      fields
+    })
+  }
+}
+
+/// An actor referred to by a line.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Actor {
+  /// The actor's ID. IDs starting with `10` are players.
+  pub id: u32,
+  /// The actor's name, which is empty if the actor has none or is unknown.
+  pub name: String
+}
+
+/// An actor's HP and MP.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Resources {
+  pub current_hp: u32,
+  pub max_hp: u32,
+  pub current_mp: u32,
+  pub max_mp: u32
+}
+
+/// Where an actor is and which way it is facing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Position {
+  pub x: f32,
+  pub y: f32,
+  pub z: f32,
+  /// The direction the actor is facing, in radians.
+  pub heading: f32
+}
+
+/// A change of zone.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ZoneChange {
+  pub timestamp: u32,
+  /// The ID of the new zone.
+  pub zone_id: u32,
+  /// The name of the new zone.
+  pub zone: String
+}
+
+impl ZoneChange {
+  fn parse(f: &Fields) -> Option<Self> {
+    Some(ZoneChange {
+      timestamp: f.timestamp,
+      zone_id: opt!(f.hex(0)),
+      zone: opt!(f.text(1))
+    })
+  }
+}
+
+/// A change of the player whose client wrote the log.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PrimaryPlayer {
+  pub timestamp: u32,
+  /// The new player.
+  pub player: Actor
+}
+
+impl PrimaryPlayer {
+  fn parse(f: &Fields) -> Option<Self> {
+    Some(PrimaryPlayer {
+      timestamp: f.timestamp,
+      player: opt!(f.actor(0))
+    })
+  }
+}
+
+/// An actor coming into or going out of range.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Combatant {
+  pub timestamp: u32,
+  /// The actor.
+  pub actor: Actor,
+  /// The actor's job, as the game's ID for it.
+  pub job: u8,
+  /// The actor's level.
+  pub level: u8,
+  /// The ID of the actor that owns this one, such as the player that summoned a pet, or 0.
+  pub owner_id: u32,
+  /// The ID of the actor's home world, or 0 if it has none.
+  pub world_id: u16,
+  /// The name of the actor's home world, which is empty if it has none.
+  pub world: String,
+  /// The ID of the actor's name, for actors that aren't players.
+  pub npc_name_id: Option<u32>,
+  /// The ID of the kind of actor, for actors that aren't players.
+  pub npc_base_id: Option<u32>,
+  /// The actor's HP and MP.
+  pub resources: Option<Resources>,
+  /// Where the actor is.
+  pub position: Option<Position>
+}
+
+impl Combatant {
+  fn parse(f: &Fields) -> Option<Self> {
+    Some(Combatant {
+      timestamp: f.timestamp,
+      actor: opt!(f.actor(0)),
+      job: opt!(f.small_hex(2)),
+      level: opt!(f.small_hex(3)),
+      owner_id: opt!(f.hex(4)),
+      world_id: opt!(f.small_hex(5)),
+      world: opt!(f.text(6)),
+      npc_name_id: f.number(7),
+      npc_base_id: f.number(8),
+      resources: f.resources(9),
+      position: f.position(15)
+    })
+  }
+}
+
+/// One of the effects of an ability on its target, such as damage or healing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AbilityEffect {
+  /// The kind of effect, and flags such as whether damage was a critical hit.
+  pub flags: u32,
+  /// The value of the effect, such as the amount of damage, in the game's packed format.
+  pub value: u32
+}
+
+/// An ability used on a target.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Ability {
+  pub timestamp: u32,
+  /// The actor that used the ability.
+  pub source: Actor,
+  /// The ID of the ability.
+  pub ability_id: u32,
+  /// The name of the ability.
+  pub ability: String,
+  /// The target of the ability, which has an ID of 0 or `E0000000` if there is none.
+  pub target: Actor,
+  /// The effects of the ability on the target. Unused effect slots are left out.
+  pub effects: Vec<AbilityEffect>,
+  /// The target's HP and MP.
+  pub target_resources: Option<Resources>,
+  /// Where the target is.
+  pub target_position: Option<Position>,
+  /// The source's HP and MP.
+  pub source_resources: Option<Resources>,
+  /// Where the source is.
+  pub source_position: Option<Position>
+}
+
+/// The number of effect slots in an ability line
+const EFFECT_SLOTS: usize = 8;
+
+impl Ability {
+  fn parse(f: &Fields) -> Option<Self> {
+    let mut effects = Vec::new();
+    for slot in 0..EFFECT_SLOTS {
+      let flags = opt!(f.hex(6 + slot * 2));
+      let value = opt!(f.hex(7 + slot * 2));
+      if flags != 0 {
+        effects.push(AbilityEffect { flags, value });
+      }
+    }
+    Some(Ability {
+      timestamp: f.timestamp,
+      source: opt!(f.actor(0)),
+      ability_id: opt!(f.hex(2)),
+      ability: opt!(f.text(3)),
+      target: opt!(f.actor(4)),
+      effects,
+      target_resources: f.resources(22),
+      target_position: f.position(28),
+      source_resources: f.resources(32),
+      source_position: f.position(38)
+    })
+  }
+}
+
+/// The death of an actor.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Death {
+  pub timestamp: u32,
+  /// The actor that died.
+  pub target: Actor,
+  /// The actor that killed it, which has an ID of 0 if there is none.
+  pub source: Actor
+}
+
+impl Death {
+  fn parse(f: &Fields) -> Option<Self> {
+    Some(Death {
+      timestamp: f.timestamp,
+      target: opt!(f.actor(0)),
+      source: opt!(f.actor(2))
+    })
+  }
+}
+
+/// A status effect gained or lost by an actor.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatusEffect {
+  pub timestamp: u32,
+  /// The ID of the status effect.
+  pub effect_id: u32,
+  /// The name of the status effect.
+  pub effect: String,
+  /// How long the status effect lasts, in seconds. This is 0 when it is lost.
+  pub duration: f32,
+  /// The actor that applied the status effect.
+  pub source: Actor,
+  /// The actor with the status effect.
+  pub target: Actor,
+  /// The number of stacks of the status effect, or extra data for effects without stacks.
+  pub count: u32
+}
+
+impl StatusEffect {
+  fn parse(f: &Fields) -> Option<Self> {
+    Some(StatusEffect {
+      timestamp: f.timestamp,
+      effect_id: opt!(f.hex(0)),
+      effect: opt!(f.text(1)),
+      duration: opt!(f.number(2)),
+      source: opt!(f.actor(3)),
+      target: opt!(f.actor(5)),
+      count: opt!(f.hex(7))
+    })
+  }
+}
+
+/// A control packet for an actor.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActorControl {
+  pub timestamp: u32,
+  /// The ID of the instance the packet applies to.
+  pub instance: u32,
+  /// What the packet does.
+  pub command: u32,
+  /// The packet's parameters, whose meaning depends on `command`.
+  pub data: [u32; 4]
+}
+
+impl ActorControl {
+  fn parse(f: &Fields) -> Option<Self> {
+    Some(ActorControl {
+      timestamp: f.timestamp,
+      instance: opt!(f.hex(0)),
+      command: opt!(f.hex(1)),
+      data: [opt!(f.hex(2)), opt!(f.hex(3)), opt!(f.hex(4)), opt!(f.hex(5))]
+    })
+  }
+}
+
+/// An update to an actor's HP and MP.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ResourceUpdate {
+  pub timestamp: u32,
+  /// The actor.
+  pub actor: Actor,
+  /// The sequence number of the ability whose effects this update applies.
+  pub sequence: u32,
+  /// The actor's HP and MP.
+  pub resources: Resources,
+  /// The actor's shield, as a percentage of its maximum HP.
+  pub shield: Option<u8>,
+  /// Where the actor is.
+  pub position: Option<Position>
+}
+
+impl ResourceUpdate {
+  fn parse(f: &Fields) -> Option<Self> {
+    Some(ResourceUpdate {
+      timestamp: f.timestamp,
+      actor: opt!(f.actor(0)),
+      sequence: opt!(f.hex(2)),
+      resources: opt!(f.resources(3)),
+      shield: f.number(7),
+      position: f.position(9)
+    })
+  }
+}
+
+/// The fields of a line, with helpers to parse them by index
+struct Fields<'a> {
+  timestamp: u32,
+  fields: &'a [String]
+}
+
+impl<'a> Fields<'a> {
+  fn new(timestamp: u32, fields: &'a [String]) -> Self {
+    Fields { timestamp, fields }
+  }
+
+  fn get(&self, i: usize) -> Option<&str> {
+    self.fields.get(i).map(|x| x.as_str())
+  }
+
+  fn text(&self, i: usize) -> Option<String> {
+    self.get(i).map(String::from)
+  }
+
+  /// Parses a field written in hexadecimal, such as an ID. An empty field is 0.
+  fn hex(&self, i: usize) -> Option<u32> {
+    match self.get(i) {
+      Some("") => Some(0),
+      Some(x) => u32::from_str_radix(x, 16).ok(),
+      None => None
+    }
+  }
+
+  /// Parses a field written in hexadecimal into a smaller integer. A value that doesn't fit fails.
+  fn small_hex<T: TryFrom<u32>>(&self, i: usize) -> Option<T> {
+    T::try_from(opt!(self.hex(i))).ok()
+  }
+
+  /// Parses a field written in decimal.
+  fn number<T: FromStr>(&self, i: usize) -> Option<T> {
+    self.get(i).and_then(|x| x.parse().ok())
+  }
+
+  /// Parses an ID followed by a name.
+  fn actor(&self, i: usize) -> Option<Actor> {
+    Some(Actor {
+      id: opt!(self.hex(i)),
+      name: opt!(self.text(i + 1))
+    })
+  }
+
+  /// Parses current and maximum HP followed by current and maximum MP.
+  fn resources(&self, i: usize) -> Option<Resources> {
+    Some(Resources {
+      current_hp: opt!(self.number(i)),
+      max_hp: opt!(self.number(i + 1)),
+      current_mp: opt!(self.number(i + 2)),
+      max_mp: opt!(self.number(i + 3))
+    })
+  }
+
+  /// Parses x, y and z coordinates followed by a heading.
+  fn position(&self, i: usize) -> Option<Position> {
+    Some(Position {
+      x: opt!(self.number(i)),
+      y: opt!(self.number(i + 1)),
+      z: opt!(self.number(i + 2)),
+      heading: opt!(self.number(i + 3))
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // The lines below are made up to follow the layouts above, not copied from a real log.
+
+  const TIME: &str = "2017-06-20T12:00:00.0000000+00:00";
+  const TIMESTAMP: u32 = 1_497_960_000;
+
+  /// Builds a line of type `kind` with `fields`, a timestamp of `TIME` and a hash.
+  fn line(kind: &str, fields: &[&str]) -> String {
+    format!("{}|{}|{}|0123456789abcdef", kind, TIME, fields.join("|"))
+  }
+
+  fn actor(id: u32, name: &str) -> Actor {
+    Actor { id, name: name.to_string() }
+  }
+
+  fn resources(current_hp: u32, max_hp: u32, current_mp: u32, max_mp: u32) -> Resources {
+    Resources { current_hp, max_hp, current_mp, max_mp }
+  }
+
+  #[test]
+  fn parses_chat() {
+    match ActLine::parse(&line("00", &["000a", "Sender", "Hello"])) {
+      Some(ActLine::Chat(entry)) => assert_eq!(entry.timestamp, TIMESTAMP),
+      l => panic!("expected a chat line, got {:?}", l)
+    }
+  }
+
+  #[test]
+  fn parses_zone_changes() {
+    match ActLine::parse(&line("01", &["84", "Ul'dah - Steps of Nald"])) {
+      Some(ActLine::ZoneChange(l)) => assert_eq!(l, ZoneChange {
+        timestamp: TIMESTAMP,
+        zone_id: 0x84,
+        zone: "Ul'dah - Steps of Nald".to_string()
+      }),
+      l => panic!("expected a zone change, got {:?}", l)
+    }
+  }
+
+  #[test]
+  fn parses_primary_players() {
+    match ActLine::parse(&line("02", &["10000001", "Sender"])) {
+      Some(ActLine::PrimaryPlayer(l)) => assert_eq!(l.player, actor(0x1000_0001, "Sender")),
+      l => panic!("expected a primary player, got {:?}", l)
+    }
+  }
+
+  #[test]
+  fn parses_combatants() {
+    let fields = [
+      "10000001", "Sender", "18", "46", "0000", "49", "Gilgamesh", "0", "0",
+      "30000", "31000", "9000", "10000", "0", "0",
+      "1.5", "-2.25", "3", "0.5"
+    ];
+    match ActLine::parse(&line("03", &fields)) {
+      Some(ActLine::AddCombatant(l)) => assert_eq!(l, Combatant {
+        timestamp: TIMESTAMP,
+        actor: actor(0x1000_0001, "Sender"),
+        job: 0x18,
+        level: 0x46,
+        owner_id: 0,
+        world_id: 0x49,
+        world: "Gilgamesh".to_string(),
+        npc_name_id: Some(0),
+        npc_base_id: Some(0),
+        resources: Some(resources(30000, 31000, 9000, 10000)),
+        position: Some(Position { x: 1.5, y: -2.25, z: 3.0, heading: 0.5 })
+      }),
+      l => panic!("expected an added combatant, got {:?}", l)
+    }
+    // Lines from older versions of the plugin end after the world
+    match ActLine::parse(&line("04", &["40000001", "Striking Dummy", "0", "1", "0000", "0", ""])) {
+      Some(ActLine::RemoveCombatant(l)) => {
+        assert_eq!(l.actor, actor(0x4000_0001, "Striking Dummy"));
+        assert_eq!(l.level, 1);
+        assert_eq!((l.npc_name_id, l.resources, l.position), (None, None, None));
+      },
+      l => panic!("expected a removed combatant, got {:?}", l)
+    }
+    // A level that doesn't fit is a bad field, not a level of 0
+    match ActLine::parse(&line("03", &["10000001", "Sender", "18", "146", "0000", "49", ""])) {
+      Some(ActLine::Raw(l)) => assert_eq!(l.kind, 3),
+      l => panic!("expected a raw line, got {:?}", l)
+    }
+    match ActLine::parse(&line("03", &["10000001", "Sender", "18", "46", "0000", "10049", ""])) {
+      Some(ActLine::Raw(l)) => assert_eq!(l.kind, 3),
+      l => panic!("expected a raw line, got {:?}", l)
+    }
+  }
+
+  #[test]
+  fn parses_abilities() {
+    let mut fields = vec!["10000001", "Sender", "1D", "Heavy Swing", "40000001", "Striking Dummy"];
+    fields.extend(&["710003", "2D10000", "1B", "0"]);
+    fields.extend(vec!["0"; 12]);
+    fields.extend(&["9000", "10000", "0", "0", "0", "0", "10", "20", "0", "1"]);
+    fields.extend(&["30000", "31000", "9000", "10000", "0", "0", "11", "21", "0", "-1"]);
+    let expected = Ability {
+      timestamp: TIMESTAMP,
+      source: actor(0x1000_0001, "Sender"),
+      ability_id: 0x1d,
+      ability: "Heavy Swing".to_string(),
+      target: actor(0x4000_0001, "Striking Dummy"),
+      effects: vec![
+        AbilityEffect { flags: 0x71_0003, value: 0x2d1_0000 },
+        AbilityEffect { flags: 0x1b, value: 0 }
+      ],
+      target_resources: Some(resources(9000, 10000, 0, 0)),
+      target_position: Some(Position { x: 10.0, y: 20.0, z: 0.0, heading: 1.0 }),
+      source_resources: Some(resources(30000, 31000, 9000, 10000)),
+      source_position: Some(Position { x: 11.0, y: 21.0, z: 0.0, heading: -1.0 })
+    };
+    match ActLine::parse(&line("21", &fields)) {
+      Some(ActLine::Ability(l)) => assert_eq!(l, expected),
+      l => panic!("expected an ability, got {:?}", l)
+    }
+    match ActLine::parse(&line("22", &fields[..22])) {
+      Some(ActLine::AoeAbility(l)) => {
+        assert_eq!(l.effects, expected.effects);
+        assert_eq!((l.target_resources, l.source_position), (None, None));
+      },
+      l => panic!("expected an AoE ability, got {:?}", l)
+    }
+  }
+
+  #[test]
+  fn parses_deaths() {
+    match ActLine::parse(&line("25", &["40000001", "Striking Dummy", "10000001", "Sender"])) {
+      Some(ActLine::Death(l)) => {
+        assert_eq!(l.target, actor(0x4000_0001, "Striking Dummy"));
+        assert_eq!(l.source, actor(0x1000_0001, "Sender"));
+      },
+      l => panic!("expected a death, got {:?}", l)
+    }
+  }
+
+  #[test]
+  fn parses_status_effects() {
+    let fields = ["31", "Regen", "21.00", "10000001", "Sender", "10000002", "Other", "00"];
+    match ActLine::parse(&line("26", &fields)) {
+      Some(ActLine::StatusGained(l)) => assert_eq!(l, StatusEffect {
+        timestamp: TIMESTAMP,
+        effect_id: 0x31,
+        effect: "Regen".to_string(),
+        duration: 21.0,
+        source: actor(0x1000_0001, "Sender"),
+        target: actor(0x1000_0002, "Other"),
+        count: 0
+      }),
+      l => panic!("expected a gained status effect, got {:?}", l)
+    }
+    let fields = ["31", "Regen", "0.00", "10000001", "Sender", "10000002", "Other", "02"];
+    match ActLine::parse(&line("30", &fields)) {
+      Some(ActLine::StatusLost(l)) => assert_eq!((l.duration, l.count), (0.0, 2)),
+      l => panic!("expected a lost status effect, got {:?}", l)
+    }
+    // Bad durations and counts are kept as raw lines rather than hidden
+    let fields = ["31", "Regen", "soon", "10000001", "Sender", "10000002", "Other", "00"];
+    match ActLine::parse(&line("26", &fields)) {
+      Some(ActLine::Raw(l)) => assert_eq!(l.kind, 26),
+      l => panic!("expected a raw line, got {:?}", l)
+    }
+    match ActLine::parse(&line("26", &fields[..7])) {
+      Some(ActLine::Raw(l)) => assert_eq!(l.kind, 26),
+      l => panic!("expected a raw line, got {:?}", l)
+    }
+  }
+
+  #[test]
+  fn parses_actor_control() {
+    match ActLine::parse(&line("33", &["80034E6C", "40000010", "1", "2", "3", "4"])) {
+      Some(ActLine::ActorControl(l)) => assert_eq!(l, ActorControl {
+        timestamp: TIMESTAMP,
+        instance: 0x8003_4e6c,
+        command: 0x4000_0010,
+        data: [1, 2, 3, 4]
+      }),
+      l => panic!("expected an actor control packet, got {:?}", l)
+    }
+  }
+
+  #[test]
+  fn parses_resource_updates() {
+    let fields = [
+      "10000001", "Sender", "00001234", "29000", "31000", "9000", "10000", "5", "",
+      "1", "2", "3", "0.5"
+    ];
+    match ActLine::parse(&line("37", &fields)) {
+      Some(ActLine::ResourceUpdate(l)) => assert_eq!(l, ResourceUpdate {
+        timestamp: TIMESTAMP,
+        actor: actor(0x1000_0001, "Sender"),
+        sequence: 0x1234,
+        resources: resources(29000, 31000, 9000, 10000),
+        shield: Some(5),
+        position: Some(Position { x: 1.0, y: 2.0, z: 3.0, heading: 0.5 })
+      }),
+      l => panic!("expected a resource update, got {:?}", l)
+    }
+  }
+
+  #[test]
+  fn keeps_other_lines_raw() {
+    match ActLine::parse(&line("251", &["a", "b"])) {
+      Some(ActLine::Raw(l)) => assert_eq!(l, RawLine {
+        kind: 251,
+        timestamp: TIMESTAMP,
+        fields: vec!["a".to_string(), "b".to_string()]
+      }),
+      l => panic!("expected a raw line, got {:?}", l)
+    }
+    assert!(ActLine::parse("not a line").is_none());
+  }
+}
@@ -0,0 +1,891 @@
+pub mod lines;
+pub mod hash;
+
+pub use self::lines::ActLine;
+pub use self::hash::HashCheck;
+
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::thread::sleep;
+use std::thread;
+use std::sync::atomic::{Ordering, AtomicBool};
+use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::mem;
+use std::time::UNIX_EPOCH;
+
+use chrono::DateTime;
+
+use messages::{Message, MessageType, Parses};
+use messages::parser::MessageParser;
+use messages::parts::NamePart;
+use messages::entries::Entry;
+use events::ReaderEvent;
+use error::ReaderError;
+use checkpoint::{Checkpoint, ActCheckpoint};
+use self::lines::RawLine;
+use channel::{self, EventReceiver, OverflowPolicy};
+use poll::PollInterval;
+
+/// A reader that extracts [`Entries`](../messages/entries/struct.Entry.html) from the network logs
+/// written by ACT.
+///
+/// This works like [`MemoryEntryReader`](../struct.MemoryEntryReader.html). If `stop` is `true`,
+/// the reader stops at the end of the log. Otherwise, it waits for more lines to be written until
+/// `stop()` is called, the reader is dropped, or whatever is receiving its entries goes away.
+///
+/// ```rust,no_run
+/// # use ffxiv_reader::ActReader;
+/// let reader = ActReader::new("Network_20170620.log", true);
+/// for entry in reader.iter() {
+///   println!("{:?}", entry);
+/// }
+/// ```
+pub struct ActReader {
+  path: PathBuf,
+  /// Whether `path` is a directory of logs to follow
+  directory: bool,
+  /// Whether the reader will stop once it reaches the end of the file.
+  ///
+  /// When following a directory, the reader first moves on to any newer log.
+  pub stop: bool,
+  /// A file to resume from and save the reader's position to.
+  ///
+  /// See [`checkpoint`](../checkpoint/index.html).
+  pub checkpoint: Option<PathBuf>,
+  /// The number of entries that can be waiting to be received before `overflow` applies.
+  ///
+  /// If this is `None`, any number of entries can be waiting.
+  pub capacity: Option<usize>,
+  /// What to do with new entries when `capacity` entries are waiting to be received.
+  pub overflow: OverflowPolicy,
+  /// How long to wait before checking for new lines after reaching the end of the file.
+  pub poll: PollInterval,
+  /// Whether to check that lines match their hashes, and what to do with lines that don't.
+  ///
+  /// Hashes depend on the number of each line, so when the reader starts anywhere but the
+  /// beginning of the log, the whole log up to that point is read to count its lines. See
+  /// [`hash`](hash/index.html).
+  pub hash_check: HashCheck,
+  /// Where to start reading when there is no checkpoint to resume from.
+  pub start_at: StartAt,
+  run: Arc<AtomicBool>
+}
+
+impl ActReader {
+  /// Creates a reader for the log file at `path`.
+  pub fn new<P: AsRef<Path>>(path: P, stop: bool) -> ActReader {
+    ActReader {
+      path: path.as_ref().to_path_buf(),
+      directory: false,
+      stop,
+      checkpoint: None,
+      capacity: None,
+      overflow: OverflowPolicy::Block,
+      poll: PollInterval::default(),
+      hash_check: HashCheck::Off,
+      start_at: StartAt::Beginning,
+      run: Arc::new(AtomicBool::new(false))
+    }
+  }
+
+  /// Creates a reader that follows the network logs ACT writes to `dir`.
+  ///
+  /// ACT starts a new `Network_*.log` file every day or session. The reader starts with the newest
+  /// log, or the log saved in its checkpoint, and once it reaches the end of a log and a newer one
+  /// has appeared, it reads anything left in the old log before moving on to the new one.
+  pub fn from_directory<P: AsRef<Path>>(dir: P, stop: bool) -> ActReader {
+    let mut reader = ActReader::new(dir, stop);
+    reader.directory = true;
+    reader
+  }
+
+  /// The path to the log file or directory being read.
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  /// The log file and byte offset that reading starts from.
+  ///
+  /// If the reader's checkpoint has been saved, this is where it was saved. When following a
+  /// directory, that is the log saved in the checkpoint, or the newest log if the checkpoint has
+  /// none. Otherwise, this is the position given by `start_at` in the reader's file, or in the log
+  /// of the directory to start from.
+  ///
+  /// This fails if the checkpoint or the file can't be read, or if a directory has no logs in it.
+  pub fn start_position(&self) -> Result<(PathBuf, u64), ReaderError> {
+    let checkpoint = match self.checkpoint {
+      Some(ref path) => ActCheckpoint::load_saved(path)?,
+      None => None
+    };
+    if let Some(checkpoint) = checkpoint {
+      if !self.directory {
+        return Ok((self.path.clone(), checkpoint.offset));
+      }
+      match checkpoint.file {
+        Some(ref file) if file.is_file() => return Ok((file.clone(), checkpoint.offset)),
+        // A checkpoint without a log is for the newest one
+        None => return Ok((first_log(&self.path, StartAt::End)?, checkpoint.offset)),
+        // The checkpoint's log is gone, so start as if there was no checkpoint
+        Some(_) => {}
+      }
+    }
+    let file = if self.directory {
+      first_log(&self.path, self.start_at)?
+    } else {
+      self.path.clone()
+    };
+    let offset = match self.start_at {
+      StartAt::Beginning => 0,
+      StartAt::End => end_position(&mut File::open(&file)?)?,
+      StartAt::Time(timestamp) => seek_time(&mut File::open(&file)?, timestamp)?
+    };
+    Ok((file, offset))
+  }
+
+  /// Starts reading the log file.
+  ///
+  /// Only chat lines are read. This will fail if `start` has already been called or if the file
+  /// can't be opened. An error reading the file is sent as an `Error` event, after which the loop
+  /// stops.
+  pub fn start(&self) -> Result<EventReceiver<Entry>, ReaderError> {
+    self.spawn(parse_line)
+  }
+
+  /// Starts reading every line of the log file.
+  ///
+  /// Chat lines are sent as `ActLine::Chat`, alongside the other types of lines. This fails in the
+  /// same ways as `start`.
+  pub fn start_lines(&self) -> Result<EventReceiver<ActLine>, ReaderError> {
+    self.spawn(ActLine::parse)
+  }
+
+  /// Starts the thread reading the file, sending each line that `parse` returns a value for.
+  fn spawn<T, F>(&self, parse: F) -> Result<EventReceiver<T>, ReaderError>
+    where T: Send + 'static,
+          F: Fn(&str) -> Option<T> + Send + 'static
+  {
+    if self.run.load(Ordering::Relaxed) {
+      return Err(ReaderError::AlreadyStarted);
+    }
+    let (mut file_path, mut offset) = self.start_position()?;
+    let mut f = File::open(&file_path)?;
+    // If the file is shorter than the checkpoint, it was replaced, so read it from the start
+    if offset > f.metadata()?.len() {
+      offset = 0;
+    }
+    let hash_check = self.hash_check;
+    // Hashes depend on the number of each line, so count the lines before the checkpoint
+    let mut line_number = match hash_check {
+      HashCheck::Off => 0,
+      _ => count_lines(&mut f, offset)?
+    };
+    f.seek(SeekFrom::Start(offset))?;
+    let checkpoint_path = self.checkpoint.clone();
+    let mut saved = (file_path.clone(), offset);
+    let directory = if self.directory { Some(self.path.clone()) } else { None };
+    let (mut tx, rx) = channel::channel(self.capacity, self.overflow);
+    tx.stop_with(self.run.clone());
+    let mut reader = BufReader::new(f);
+    let stop = self.stop;
+    let mut timer = self.poll.timer()?;
+    self.run.store(true, Ordering::Relaxed);
+    let run = self.run.clone();
+    thread::spawn(move || {
+      let mut con = String::new();
+      // Whether another file was found at the end of this one, which is being read to its end once
+      // more in case anything was written to it in the meantime
+      let mut draining = false;
+      while run.load(Ordering::Relaxed) {
+        let size = match reader.read_line(&mut con) {
+          Ok(s) => s,
+          Err(e) => {
+            tx.send(ReaderEvent::Error(ReaderError::Io(e))).ok();
+            break;
+          }
+        };
+        // A line without its newline is still being written, so it is kept until the rest of it has
+        // been read. Only complete lines move the position on.
+        if size == 0 || !con.ends_with('\n') {
+          let dir = directory.as_deref();
+          match check_file(reader.get_ref(), &file_path, offset, dir) {
+            Ok(FileChange::Unchanged) => {},
+            Ok(FileChange::Truncated) => {
+              offset = 0;
+              line_number = 0;
+              con.clear();
+              if let Err(e) = reader.seek(SeekFrom::Start(0)) {
+                tx.send(ReaderEvent::Error(ReaderError::Io(e))).ok();
+                break;
+              }
+              continue;
+            },
+            Ok(FileChange::Replaced(_)) if !draining => {
+              draining = true;
+              continue;
+            },
+            Ok(FileChange::Replaced(path)) => {
+              draining = false;
+              match File::open(&path) {
+                Ok(f) => reader = BufReader::new(f),
+                Err(e) => {
+                  tx.send(ReaderEvent::Error(ReaderError::Io(e))).ok();
+                  break;
+                }
+              }
+              file_path = path;
+              offset = 0;
+              line_number = 0;
+              // A line left unfinished in the old log will never be finished
+              con.clear();
+              continue;
+            },
+            Err(e) => {
+              tx.send(ReaderEvent::Error(ReaderError::Io(e))).ok();
+              break;
+            }
+          }
+          // Save the position once caught up with the file
+          let changed = offset != saved.1 || file_path != saved.0;
+          if let (true, Some(path)) = (changed, checkpoint_path.as_ref()) {
+            saved = (file_path.clone(), offset);
+            let checkpoint = ActCheckpoint { offset, file: Some(file_path.clone()) };
+            if let Err(e) = Checkpoint::Act(checkpoint).save(path) {
+              try_or!(tx.send(ReaderEvent::Error(ReaderError::Io(e))), break);
+            }
+          }
+          // Nothing will be sent until more lines are written, so check if there is still anything
+          // to send them to. A line still being written when stopping isn't sent, and is read once
+          // it is finished by a reader resuming from the checkpoint.
+          if stop || tx.is_disconnected() {
+            break;
+          } else {
+            sleep(timer.idle());
+            continue;
+          }
+        }
+        timer.found();
+        offset += con.len() as u64;
+        line_number += 1;
+        let content = mem::take(&mut con);
+
+        if hash_check != HashCheck::Off && !hash::verify_line(&content, line_number) {
+          let error = ReaderError::InvalidLineHash {
+            file: file_path.clone(),
+            line: line_number
+          };
+          try_or!(tx.send(ReaderEvent::Error(error)), break);
+          if hash_check == HashCheck::Reject {
+            continue;
+          }
+        }
+
+        let item = match parse(&content) {
+          Some(i) => i,
+          None => continue
+        };
+        try_or!(tx.send(ReaderEvent::Item(item)), break);
+      }
+    });
+    Ok(rx)
+  }
+
+  /// Stops reading the log file.
+  ///
+  /// This is called automatically when the reader is dropped.
+  pub fn stop(&self) {
+    self.run.store(false, Ordering::Relaxed);
+  }
+
+  /// Creates an iterator over the chat entries in the log.
+  ///
+  /// This automatically calls `start`.
+  ///
+  /// If `start` has been called but `stop` has not been called, the iterator returned will always
+  /// return `None`.
+  pub fn iter(&self) -> ActReaderIterator {
+    match self.start() {
+      Ok(rx) => ActReaderIterator { rx: Some(rx), error: None },
+      Err(e) => ActReaderIterator { rx: None, error: Some(e) }
+    }
+  }
+}
+
+impl Drop for ActReader {
+  fn drop(&mut self) {
+    self.stop();
+  }
+}
+
+/// The iterator for [`ActReader`](struct.ActReader.html).
+///
+/// Errors are not returned by the iterator. The last error encountered, including any error from
+/// starting the reader, can be checked with `error()`.
+pub struct ActReaderIterator {
+  rx: Option<EventReceiver<Entry>>,
+  error: Option<ReaderError>
+}
+
+impl ActReaderIterator {
+  /// Gets the last error encountered by the reader, if any.
+  pub fn error(&self) -> Option<&ReaderError> {
+    self.error.as_ref()
+  }
+}
+
+impl Iterator for ActReaderIterator {
+  type Item = Entry;
+
+  fn next(&mut self) -> Option<Entry> {
+    let rx = match self.rx {
+      Some(ref r) => r,
+      None => return None
+    };
+    loop {
+      match rx.recv() {
+        Ok(ReaderEvent::Item(entry)) => return Some(entry),
+        Ok(ReaderEvent::Error(e)) => self.error = Some(e),
+        Ok(_) => continue,
+        Err(_) => return None
+      }
+    }
+  }
+}
+
+/// Where an [`ActReader`](struct.ActReader.html) without a checkpoint starts reading.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartAt {
+  /// Read the whole log.
+  Beginning,
+  /// Only read lines written after the reader starts.
+  End,
+  /// Start at the first line written at or after a timestamp, in seconds since the Unix epoch.
+  ///
+  /// Lines in a log are in order, so the line is found with a binary search instead of reading the
+  /// log up to it, unless `hash_check` is on. When following a directory, the reader starts with
+  /// the oldest log that was written to at or after the timestamp.
+  Time(u32)
+}
+
+/// Picks the log in `dir` to start reading from.
+///
+/// This is the newest log, unless starting at a time. Then, it is the oldest log written to at or
+/// after that time, which is the first one that can have lines from then on.
+fn first_log(dir: &Path, start_at: StartAt) -> io::Result<PathBuf> {
+  let mut logs = find_logs(dir)?;
+  if let StartAt::Time(timestamp) = start_at {
+    let modified = |path: &PathBuf| {
+      fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+    };
+    if let Some(i) = logs.iter().position(|l| modified(l) >= timestamp as u64) {
+      return Ok(logs.swap_remove(i));
+    }
+  }
+  match logs.pop() {
+    Some(newest) => Ok(newest),
+    None => {
+      let message = format!("no network logs in {}", dir.display());
+      Err(io::Error::new(io::ErrorKind::NotFound, message))
+    }
+  }
+}
+
+/// Finds the end of the last complete line in `file`, so a line still being written is read whole.
+fn end_position(file: &mut File) -> io::Result<u64> {
+  let mut end = file.metadata()?.len();
+  let mut buf = [0; 4096];
+  while end > 0 {
+    let start = end.saturating_sub(buf.len() as u64);
+    let chunk = &mut buf[..(end - start) as usize];
+    file.seek(SeekFrom::Start(start))?;
+    file.read_exact(chunk)?;
+    if let Some(i) = chunk.iter().rposition(|b| *b == b'\n') {
+      return Ok(start + i as u64 + 1);
+    }
+    end = start;
+  }
+  Ok(0)
+}
+
+/// Finds the start of the first line in `file` written at or after `timestamp`, or the end of the
+/// last complete line if there is none.
+///
+/// This is a binary search over byte offsets. Each step reads the first line starting at or after
+/// the middle of the range left, so only a few lines are read. A line still being written is left
+/// out of the search, so the reader reads it whole.
+fn seek_time(file: &mut File, timestamp: u32) -> io::Result<u64> {
+  // The line being looked for starts at or after `low`, which is always the start of a line, and at
+  // or before the first line starting at or after `high`
+  let mut low = 0;
+  let mut high = end_position(file)?;
+  while low < high {
+    let middle = low + (high - low) / 2;
+    let start = next_line_start(file, middle)?;
+    if start >= high {
+      // No lines start between the middle and the end of the range
+      high = middle;
+      continue;
+    }
+    let (time, end) = read_line_time(file, start)?;
+    match time {
+      Some(t) if t >= timestamp => high = middle,
+      // Lines without a timestamp are treated as earlier ones
+      _ => low = end
+    }
+  }
+  Ok(low)
+}
+
+/// Finds the start of the first line starting at or after `position`.
+fn next_line_start(file: &mut File, position: u64) -> io::Result<u64> {
+  if position == 0 {
+    return Ok(0);
+  }
+  // Skip the rest of the line holding the byte before the position
+  file.seek(SeekFrom::Start(position - 1))?;
+  let skipped = BufReader::new(file.by_ref()).read_until(b'\n', &mut Vec::new())?;
+  Ok(position - 1 + skipped as u64)
+}
+
+/// Reads the timestamp of the line starting at `start`, along with where the next line starts.
+fn read_line_time(file: &mut File, start: u64) -> io::Result<(Option<u32>, u64)> {
+  file.seek(SeekFrom::Start(start))?;
+  let mut line = Vec::new();
+  let read = BufReader::new(file.by_ref()).read_until(b'\n', &mut line)?;
+  let time = RawLine::parse(&String::from_utf8_lossy(&line)).map(|l| l.timestamp);
+  Ok((time, start + read as u64))
+}
+
+/// Finds the network logs ACT has written to `dir`, oldest first.
+///
+/// Network logs are the files named `Network_*.log`. They are ordered by when they were last
+/// written to, then by name.
+pub fn find_logs<P: AsRef<Path>>(dir: P) -> io::Result<Vec<PathBuf>> {
+  let mut logs = Vec::new();
+  for entry in fs::read_dir(dir)? {
+    let entry = entry?;
+    let is_log = entry.file_name()
+      .to_str()
+      .map(|n| n.starts_with("Network_") && n.ends_with(".log"))
+      .unwrap_or(false);
+    if !is_log {
+      continue;
+    }
+    // Skip anything that isn't a file, or that was removed since the directory was listed
+    let metadata = try_or!(entry.metadata(), continue);
+    if !metadata.is_file() {
+      continue;
+    }
+    let modified = try_or!(metadata.modified(), continue);
+    logs.push((modified, entry.path()));
+  }
+  logs.sort();
+  Ok(logs.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Counts the lines in the first `length` bytes of `file`, leaving it just after them.
+fn count_lines(file: &mut File, length: u64) -> io::Result<u64> {
+  file.seek(SeekFrom::Start(0))?;
+  let mut reader = BufReader::new(file.by_ref().take(length));
+  let mut lines = 0;
+  loop {
+    let read = {
+      let buf = reader.fill_buf()?;
+      lines += buf.iter().filter(|b| **b == b'\n').count() as u64;
+      buf.len()
+    };
+    if read == 0 {
+      return Ok(lines);
+    }
+    reader.consume(read);
+  }
+}
+
+/// What changed about the file being read, found once the end of it was reached
+enum FileChange {
+  /// Nothing, so more lines may still be written to it
+  Unchanged,
+  /// It was truncated, so it should be read again from the start
+  Truncated,
+  /// Another file should be read from the start instead, because the file was replaced or, when
+  /// following a directory, a newer log was found
+  Replaced(PathBuf)
+}
+
+/// Checks whether `file`, opened from `path` and read up to `offset`, should still be read.
+fn check_file(file: &File,
+              path: &Path,
+              offset: u64,
+              directory: Option<&Path>) -> io::Result<FileChange> {
+  let open = file.metadata()?;
+  if open.len() < offset {
+    return Ok(FileChange::Truncated);
+  }
+  // A log that was moved or removed may still be followed by a newer one, so only a different
+  // file at the same path counts as a replacement
+  if let Ok(current) = fs::metadata(path) {
+    if !same_file(&open, &current) {
+      return Ok(FileChange::Replaced(path.to_path_buf()));
+    }
+  }
+  let dir = match directory {
+    Some(d) => d,
+    None => return Ok(FileChange::Unchanged)
+  };
+  let logs = find_logs(dir)?;
+  let next = match logs.iter().position(|l| l == path) {
+    Some(i) => logs.get(i + 1),
+    None => logs.last()
+  };
+  Ok(match next {
+    Some(next) => FileChange::Replaced(next.clone()),
+    None => FileChange::Unchanged
+  })
+}
+
+/// Checks if two sets of metadata are for the same file.
+#[cfg(unix)]
+fn same_file(a: &Metadata, b: &Metadata) -> bool {
+  use std::os::unix::fs::MetadataExt;
+  a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+/// Checks if two sets of metadata are for the same file.
+///
+/// Without inode numbers, files are compared by when they were created.
+#[cfg(not(unix))]
+fn same_file(a: &Metadata, b: &Metadata) -> bool {
+  match (a.created(), b.created()) {
+    (Ok(a), Ok(b)) => a == b,
+    _ => true
+  }
+}
+
+/// Parses a line from an ACT log into an entry.
+///
+/// Returns `None` if the line is not a chat line or is invalid.
+pub fn parse_line(line: &str) -> Option<Entry> {
+  let mut parts = line.split('|');
+
+  let act_internal = parts.next(); // maybe?
+  if act_internal != Some("00") {
+    return None;
+  }
+
+  let datetime = opt!(parts.next().and_then(|x| DateTime::parse_from_rfc3339(x).ok()));
+  let timestamp = datetime.timestamp() as u32;
+
+  let kind = opt!(parts.next().and_then(|x| u64::from_str_radix(x, 16).ok()));
+  let message_type = MessageType::from((kind & 0xFF) as u8);
+
+  let sender = parts.next().and_then(|x| NamePart::parse(x.as_bytes()));
+
+  // The last part is the hash of the line
+  let left_over: Vec<_> = parts.collect();
+  if left_over.is_empty() {
+    return None;
+  }
+  let message_str = left_over[..left_over.len() - 1].join("|");
+  let message_parts = MessageParser::parse(message_str.as_bytes());
+  let message = Message::new(message_parts);
+
+  Some(Entry {
+    message_type,
+    timestamp,
+    sender,
+    message,
+    outgoing: false
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use self::hash::HashAlgorithm;
+  use messages::HasDisplayText;
+
+  use std::env;
+  use std::fs::OpenOptions;
+  use std::io::Write;
+  use std::sync::mpsc::RecvTimeoutError;
+  use std::time::{Duration, UNIX_EPOCH};
+
+  /// Writes `content` to a file in the temporary directory, unique to the test named `name`.
+  fn write_log(name: &str, content: &str) -> PathBuf {
+    let name = format!("ffxiv_reader_act_{}_{}.log", name, ::std::process::id());
+    let path = env::temp_dir().join(name);
+    File::create(&path).unwrap().write_all(content.as_bytes()).unwrap();
+    path
+  }
+
+  fn append(path: &Path, content: &str) {
+    OpenOptions::new().append(true).open(path).unwrap().write_all(content.as_bytes()).unwrap();
+  }
+
+  /// Builds a chat line with the message `message`, ending with a line break.
+  fn chat_line(message: &str) -> String {
+    format!("00|2017-06-20T12:00:00.0000000+00:00|000a|Sender|{}|0123456789abcdef\n", message)
+  }
+
+  /// Reads the messages of the chat lines the reader sends until it stops.
+  fn read_messages(reader: &ActReader) -> Vec<String> {
+    reader.start().unwrap().iter()
+      .filter_map(|event| match event {
+        ReaderEvent::Item(entry) => Some(entry.message.display_text()),
+        _ => None
+      })
+      .collect()
+  }
+
+  #[test]
+  fn resumes_from_checkpoints() {
+    let path = write_log("resume", &format!("{}{}", chat_line("one"), chat_line("two")));
+    let checkpoint = env::temp_dir()
+      .join(format!("ffxiv_reader_act_resume_{}.checkpoint", ::std::process::id()));
+    let reader = |stop| {
+      let mut reader = ActReader::new(&path, stop);
+      reader.checkpoint = Some(checkpoint.clone());
+      reader
+    };
+    assert_eq!(read_messages(&reader(true)), vec!["one", "two"]);
+    let length = fs::metadata(&path).unwrap().len();
+    assert_eq!(ActCheckpoint::load(&checkpoint).unwrap().offset, length);
+    append(&path, &chat_line("three"));
+    assert_eq!(read_messages(&reader(true)), vec!["three"]);
+    assert!(read_messages(&reader(true)).is_empty());
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&checkpoint).unwrap();
+  }
+
+  /// Creates an empty directory for logs, unique to the test named `name`.
+  fn log_dir(name: &str) -> PathBuf {
+    let name = format!("ffxiv_reader_act_dir_{}_{}", name, ::std::process::id());
+    let dir = env::temp_dir().join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+  }
+
+  /// Writes a log to `dir` that was last written to at the Unix time `modified`.
+  fn write_dated_log(dir: &Path, name: &str, content: &str, modified: u64) -> PathBuf {
+    let path = dir.join(name);
+    let mut f = File::create(&path).unwrap();
+    f.write_all(content.as_bytes()).unwrap();
+    f.set_modified(UNIX_EPOCH + Duration::from_secs(modified)).unwrap();
+    path
+  }
+
+  #[test]
+  fn finds_logs_oldest_first() {
+    let dir = log_dir("find");
+    let b = write_dated_log(&dir, "Network_b.log", "", 1_500_000_000);
+    let c = write_dated_log(&dir, "Network_c.log", "", 1_500_000_010);
+    let a = write_dated_log(&dir, "Network_a.log", "", 1_500_000_010);
+    write_dated_log(&dir, "notes.txt", "", 1_500_000_020);
+    fs::create_dir(dir.join("Network_d.log")).unwrap();
+    assert_eq!(find_logs(&dir).unwrap(), vec![b, a, c]);
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn follows_newer_logs() {
+    let dir = log_dir("follow");
+    let first = write_dated_log(&dir, "Network_1.log", &chat_line("one"), 1_500_000_000);
+    let second = write_dated_log(&dir, "Network_2.log", &chat_line("two"), 1_500_000_010);
+    // Without a checkpoint, only the newest log is read
+    assert_eq!(read_messages(&ActReader::from_directory(&dir, true)), vec!["two"]);
+    let checkpoint = dir.join("reader.checkpoint");
+    let saved = ActCheckpoint { offset: 0, file: Some(first) };
+    Checkpoint::Act(saved).save(&checkpoint).unwrap();
+    let mut reader = ActReader::from_directory(&dir, true);
+    reader.checkpoint = Some(checkpoint.clone());
+    assert_eq!(read_messages(&reader), vec!["one", "two"]);
+    let saved = ActCheckpoint::load(&checkpoint).unwrap();
+    assert_eq!(saved.file, Some(second));
+    assert_eq!(saved.offset, chat_line("two").len() as u64);
+    fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn rereads_replaced_logs() {
+    let path = write_log("replace", &format!("{}{}", chat_line("one"), chat_line("two")));
+    let checkpoint = env::temp_dir()
+      .join(format!("ffxiv_reader_act_replace_{}.checkpoint", ::std::process::id()));
+    let reader = || {
+      let mut reader = ActReader::new(&path, true);
+      reader.checkpoint = Some(checkpoint.clone());
+      reader
+    };
+    assert_eq!(read_messages(&reader()), vec!["one", "two"]);
+    // The new log is shorter than where the checkpoint is, so it is read from the start
+    File::create(&path).unwrap().write_all(chat_line("three").as_bytes()).unwrap();
+    assert_eq!(read_messages(&reader()), vec!["three"]);
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&checkpoint).unwrap();
+  }
+
+  #[test]
+  fn iterates_over_chat_entries() {
+    let path = write_log("iter", &format!("{}{}", chat_line("one"), chat_line("two")));
+    let reader = ActReader::new(&path, true);
+    let mut iter = reader.iter();
+    let messages: Vec<String> = iter.by_ref().map(|e| e.message.display_text()).collect();
+    assert_eq!(messages, vec!["one", "two"]);
+    assert!(iter.error().is_none());
+    fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn iterators_keep_start_errors() {
+    let path = env::temp_dir()
+      .join(format!("ffxiv_reader_act_missing_{}.log", ::std::process::id()));
+    let reader = ActReader::new(&path, true);
+    let mut iter = reader.iter();
+    assert!(iter.next().is_none());
+    match iter.error() {
+      Some(&ReaderError::Io(_)) => {},
+      e => panic!("expected an I/O error, got {:?}", e)
+    }
+  }
+
+  #[test]
+  fn stops_when_dropped() {
+    let path = write_log("drop", &chat_line("one"));
+    let mut reader = ActReader::new(&path, false);
+    reader.poll = PollInterval::Fixed(Duration::from_millis(5));
+    let rx = reader.start().unwrap();
+    assert!(rx.recv_timeout(Duration::from_secs(5)).map(|e| e.is_item()).unwrap_or(false));
+    drop(reader);
+    // The reading thread drops its sender as it exits
+    loop {
+      match rx.recv_timeout(Duration::from_secs(5)) {
+        Ok(_) => continue,
+        Err(RecvTimeoutError::Disconnected) => break,
+        Err(e) => panic!("the reader kept running: {:?}", e)
+      }
+    }
+    fs::remove_file(&path).unwrap();
+  }
+
+  /// Builds line number `number` of a log, of a type with no struct of its own, ending with its
+  /// hash and a line break.
+  fn hashed_line(number: u64, field: &str) -> String {
+    let content = format!("251|2017-06-20T12:00:00.0000000+00:00|{}|", field);
+    let hash = hash::line_hash(&content, number, HashAlgorithm::Sha256);
+    format!("{}{}\n", content, hash)
+  }
+
+  /// Receives the fields of the next line, failing on anything else.
+  fn next_fields(rx: &EventReceiver<ActLine>) -> Vec<String> {
+    match rx.recv_timeout(Duration::from_secs(5)) {
+      Ok(ReaderEvent::Item(ActLine::Raw(line))) => line.fields,
+      e => panic!("expected a line, got {:?}", e)
+    }
+  }
+
+  #[test]
+  fn waits_for_lines_to_be_finished() {
+    let second = hashed_line(2, "second");
+    let (start, rest) = second.split_at(20);
+    let path = write_log("follow", &format!("{}{}", hashed_line(1, "first"), start));
+    let mut reader = ActReader::new(&path, false);
+    reader.hash_check = HashCheck::Reject;
+    reader.poll = PollInterval::Fixed(Duration::from_millis(5));
+    let rx = reader.start_lines().unwrap();
+    assert_eq!(next_fields(&rx), vec!["first"]);
+    assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+    append(&path, rest);
+    assert_eq!(next_fields(&rx), vec!["second"]);
+    // The line numbers are still right for the lines after it
+    append(&path, &hashed_line(3, "third"));
+    assert_eq!(next_fields(&rx), vec!["third"]);
+    reader.stop();
+    fs::remove_file(&path).unwrap();
+  }
+
+  /// Builds a line written at `timestamp`, which must be within the first day of 1970, ending with
+  /// a line break.
+  fn timed_line(timestamp: u32) -> String {
+    let (hours, minutes, seconds) = (timestamp / 3600, timestamp / 60 % 60, timestamp % 60);
+    let time = format!("1970-01-01T{:02}:{:02}:{:02}Z", hours, minutes, seconds);
+    format!("251|{}|{}|0123456789abcdef\n", time, timestamp)
+  }
+
+  #[test]
+  fn finds_the_end_of_complete_lines() {
+    let first = timed_line(100);
+    let path = write_log("end", &format!("{}{}", first, timed_line(200)));
+    let length = fs::metadata(&path).unwrap().len();
+    assert_eq!(end_position(&mut File::open(&path).unwrap()).unwrap(), length);
+    // A line still being written is left for the reader to read whole
+    append(&path, "251|1970-01-01T00:05:00");
+    assert_eq!(end_position(&mut File::open(&path).unwrap()).unwrap(), length);
+    fs::remove_file(&path).unwrap();
+    let path = write_log("end_unfinished", "251|1970-01-01T00:05:00");
+    assert_eq!(end_position(&mut File::open(&path).unwrap()).unwrap(), 0);
+    fs::remove_file(&path).unwrap();
+    let path = write_log("end_empty", "");
+    assert_eq!(end_position(&mut File::open(&path).unwrap()).unwrap(), 0);
+    fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn seeks_to_times() {
+    let lines: Vec<String> = [100, 200, 200, 300, 400].iter().map(|t| timed_line(*t)).collect();
+    let start = |i: usize| lines[..i].iter().map(|l| l.len() as u64).sum::<u64>();
+    let path = write_log("seek", &lines.concat());
+    let seek = |timestamp| seek_time(&mut File::open(&path).unwrap(), timestamp).unwrap();
+    // Before the first line
+    assert_eq!(seek(50), 0);
+    assert_eq!(seek(100), 0);
+    assert_eq!(seek(150), start(1));
+    // The first of several lines written at the same time
+    assert_eq!(seek(200), start(1));
+    assert_eq!(seek(201), start(3));
+    assert_eq!(seek(400), start(4));
+    // After the last line
+    assert_eq!(seek(500), start(5));
+    // A line still being written isn't searched, even if it would be the line found
+    append(&path, "251|1970-01-01T00:08:20");
+    assert_eq!(seek(500), start(5));
+    assert_eq!(seek(600), start(5));
+    assert_eq!(seek(300), start(3));
+    fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn skips_unfinished_lines_when_stopping() {
+    let first = hashed_line(1, "first");
+    let second = hashed_line(2, "second");
+    let (start, rest) = second.split_at(20);
+    let path = write_log("unfinished", &format!("{}{}", first, start));
+    let checkpoint = env::temp_dir()
+      .join(format!("ffxiv_reader_act_unfinished_{}.checkpoint", ::std::process::id()));
+    let reader = || {
+      let mut reader = ActReader::new(&path, true);
+      reader.checkpoint = Some(checkpoint.clone());
+      reader.hash_check = HashCheck::Reject;
+      reader
+    };
+    let first_reader = reader();
+    let rx = first_reader.start_lines().unwrap();
+    assert_eq!(next_fields(&rx), vec!["first"]);
+    assert!(rx.recv().is_err());
+    let saved = ActCheckpoint::load_saved(&checkpoint).unwrap().unwrap();
+    assert_eq!(saved.offset, first.len() as u64);
+    // Once finished, the line is read whole, and only once
+    append(&path, rest);
+    let second_reader = reader();
+    let rx = second_reader.start_lines().unwrap();
+    assert_eq!(next_fields(&rx), vec!["second"]);
+    assert!(rx.recv().is_err());
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&checkpoint).unwrap();
+  }
+}
@@ -0,0 +1,102 @@
+extern crate ffxiv_reader;
+
+use ffxiv_reader::{MemorySource, OffsetProfile, ProcessMemory};
+use ffxiv_reader::snapshot::{self, SnapshotWriter, Frame};
+
+use std::env::args;
+use std::fs::File;
+use std::io::BufWriter;
+use std::thread::sleep;
+use std::time::Duration;
+
+// Captures the chat log's memory to a snapshot file until killed. A frame is written whenever the
+// memory changes, so the file can be replayed with replay_snapshot to reproduce problems without
+// the game running.
+
+fn main() {
+  // Gather the arguments supplied to the program.
+  let args: Vec<String> = args().skip(1).collect();
+  // Ensure they are not empty.
+  if args.len() < 2 {
+    println!("Please supply a PID or process name (e.g. ffxiv_dx11.exe) and an output path.");
+    return;
+  }
+  // Use the first arg as a PID, or find the process by name if it isn't one.
+  let source = match args[0].parse() {
+    Ok(pid) => ProcessMemory::new(pid),
+    Err(_) => match ProcessMemory::from_name(&args[0]) {
+      Some(s) => s,
+      None => {
+        println!("Could not find a process named {}.", args[0]);
+        return;
+      }
+    }
+  };
+  // Get the interval between captures in milliseconds.
+  let interval = if args.len() > 2 {
+    match args[2].parse() {
+      Ok(i) => Duration::from_millis(i),
+      Err(e) => {
+        println!("Invalid interval: {}.", e);
+        return;
+      }
+    }
+  } else {
+    Duration::from_millis(100)
+  };
+  // Load an offset profile if one was specified.
+  let profile = if args.len() > 3 {
+    match OffsetProfile::from_file(&args[3]) {
+      Ok(p) => p,
+      Err(e) => {
+        println!("Could not load offset profile {}: {}", args[3], e);
+        return;
+      }
+    }
+  } else {
+    OffsetProfile::default()
+  };
+  // Resolve the profile once, so the module image doesn't need to be scanned for every frame.
+  let (profile, module_base) = match snapshot::capture_profile(&source, &profile) {
+    Some(p) => p,
+    None => {
+      println!("Could not find {} in the process.", profile.module);
+      return;
+    }
+  };
+  let file = match File::create(&args[1]) {
+    Ok(f) => f,
+    Err(e) => {
+      println!("Could not create {}: {}", args[1], e);
+      return;
+    }
+  };
+  let mut writer = match SnapshotWriter::new(BufWriter::new(file), &profile, module_base) {
+    Ok(w) => w,
+    Err(e) => {
+      println!("Could not write to {}: {}", args[1], e);
+      return;
+    }
+  };
+  let mut last_frame: Option<Frame> = None;
+  loop {
+    // Skip frames that can't be read, such as while the game is logging in.
+    let frame = match snapshot::capture_frame(&source, &profile) {
+      Some(f) => f,
+      None => {
+        source.reattach();
+        sleep(interval);
+        continue;
+      }
+    };
+    // Only write frames that changed.
+    if last_frame.as_ref().map(|l| !l.same_memory(&frame)).unwrap_or(true) {
+      if let Err(e) = writer.write_frame(&frame) {
+        println!("Could not write to {}: {}", args[1], e);
+        return;
+      }
+      last_frame = Some(frame);
+    }
+    sleep(interval);
+  }
+}
@@ -0,0 +1,86 @@
+extern crate ffxiv_reader;
+extern crate serde_json;
+
+use ffxiv_reader::process::{self, GAME_PROCESS_NAME};
+use ffxiv_reader::discovery::{self, DiscoveryOptions, MemoryDump};
+use ffxiv_reader::snapshot::Snapshot;
+
+use std::env::args;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+// Finds the offsets of the chat log after a game patch. Send a line in chat, then run this with
+// the game's PID or a snapshot and the message of that line. The best offset profile found is
+// printed as JSON, ready to be loaded with OffsetProfile::from_file. Other profiles found are
+// listed after it on stderr.
+//
+// When reading a running game, its memory can be saved as a snapshot to search again later. Only
+// snapshots saved this way can be searched: those recorded by capture_snapshot hold the chat log
+// but not the game's module, so no pointer chains can be found from them. Saving copies all of the
+// game's memory first, so it needs as much free RAM as the game uses.
+
+fn main() {
+  // Gather the arguments supplied to the program.
+  let args: Vec<String> = args().skip(1).collect();
+  // Ensure they are not empty.
+  if args.len() < 2 {
+    println!("Please supply a PID, process name (e.g. ffxiv_dx11.exe) or snapshot path, and the \
+              message of a line just sent in chat.");
+    return;
+  }
+  // Use the first arg as a PID, a snapshot file if one exists there, or a process name.
+  let pid = match args[0].parse() {
+    Ok(pid) => Some(pid),
+    Err(_) if Path::new(&args[0]).is_file() => None,
+    Err(_) => match process::find_pid(&args[0]) {
+      Some(pid) => Some(pid),
+      None => {
+        println!("Could not find a process named {}.", args[0]);
+        return;
+      }
+    }
+  };
+  let mut dump = match pid {
+    Some(pid) => match MemoryDump::from_process(pid, GAME_PROCESS_NAME) {
+      Ok(d) => d,
+      Err(e) => {
+        println!("Could not read memory: {}", e);
+        return;
+      }
+    },
+    None => match Snapshot::from_file(&args[0]) {
+      Ok(s) => MemoryDump::from_snapshot(s),
+      Err(e) => {
+        println!("Could not load snapshot {}: {}", args[0], e);
+        return;
+      }
+    }
+  };
+  if pid.is_none() && !dump.has_module_memory() {
+    println!("Snapshot {} does not hold the memory of {}, so no pointer chains can be found in it. \
+              Only snapshots saved by find_profile can be searched.", args[0], dump.module);
+    return;
+  }
+  // Save the memory if a path was specified, and search the copy that was saved.
+  if args.len() > 2 {
+    dump = dump.copy();
+    if let Err(e) = File::create(&args[2]).and_then(|f| dump.save(BufWriter::new(f))) {
+      println!("Could not save memory to {}: {}", args[2], e);
+      return;
+    }
+  }
+  let profiles = discovery::discover(&dump, &args[1], &DiscoveryOptions::default());
+  let best = match profiles.first() {
+    Some(p) => p,
+    None => {
+      println!("Could not find the chat log. Make sure the line was the last one in the chat log, \
+                and not the first.");
+      return;
+    }
+  };
+  println!("{}", serde_json::to_string_pretty(best).unwrap());
+  for profile in &profiles[1..] {
+    eprintln!("Also found: {}", serde_json::to_string(profile).unwrap());
+  }
+}
@@ -10,28 +10,14 @@ use std::env::args;
 // any new messages it hasn't read before by reading from the chat log in memory at the index
 // locations.
 
-// TODO: Investigate what happens when the memory fills up and starts from the beginning again.
-//       Written what I think is a reasonable bit of logic for handling it, but I haven't actually
-//       tested it.
-// TODO: Handle the game closing, logging out, disconnects, etc. better. Wait for pointers to become
-//       valid again, then start reading again.
-
 fn main() {
   // Gather the arguments supplied to the program.
   let args: Vec<String> = args().skip(1).collect();
   // Ensure they are not empty.
   if args.is_empty() {
-    println!("Please supply a PID.");
+    println!("Please supply a PID or process name (e.g. ffxiv_dx11.exe).");
     return;
   }
-  // Attempt to parse a PID from the first arg.
-  let pid: u32 = match args[0].parse() {
-    Ok(p) => p,
-    Err(e) => {
-      println!("Invalid PID: {}.", e);
-      return;
-    }
-  };
   // Check whether the program should continue scanning memory or just stop.
   let stop = if args.len() > 1 {
     match args[1].to_lowercase().parse() {
@@ -42,10 +28,43 @@ fn main() {
       }
     }
   } else { false };
-  // Create a log reader.
-  let reader = MemoryEntryReader::new(pid, stop);
-  // Print out every entry.
-  for entry in reader.iter() {
-    println!("{}", serde_json::to_string(&entry).unwrap());
+  // Create a log reader, using the first arg as a PID or finding the process by name.
+  let mut reader = match args[0].parse() {
+    Ok(pid) => MemoryEntryReader::new(pid, stop),
+    Err(_) => match MemoryEntryReader::from_process_name(&args[0], stop) {
+      Some(r) => r,
+      None => {
+        println!("Could not find a process named {}.", args[0]);
+        return;
+      }
+    }
+  };
+  // Keep waiting for the game if it closes, logs out or disconnects while following the log.
+  reader.reconnect = !stop;
+  // Load an offset profile if one was specified, and use it whatever the game's version.
+  if args.len() > 2 {
+    reader.profile = match OffsetProfile::from_file(&args[2]) {
+      Ok(p) => p,
+      Err(e) => {
+        println!("Could not load offset profile {}: {}", args[2], e);
+        return;
+      }
+    };
+    reader.registry = None;
+  }
+  let rx = match reader.start() {
+    Ok(rx) => rx,
+    Err(e) => {
+      println!("Error reading memory: {}", e);
+      return;
+    }
+  };
+  // Print out every entry, and any errors as they happen so a stalled follow can be diagnosed.
+  for event in rx {
+    match event {
+      ReaderEvent::Item(entry) => println!("{}", serde_json::to_string(&entry).unwrap()),
+      ReaderEvent::Error(e) => println!("Error reading memory: {}", e),
+      _ => {}
+    }
   }
 }
@@ -4,6 +4,7 @@ extern crate serde_json;
 use ffxiv_reader::*;
 
 use std::env::args;
+use std::path::Path;
 
 // The main loop checks the game's memory for a list of indices that point to where messages start
 // in the chat log kept in memory. The loop checks for new indices by checking a pointer, then reads
@@ -21,7 +22,7 @@ fn main() {
   let args: Vec<String> = args().skip(1).collect();
   // Ensure they are not empty.
   if args.is_empty() {
-    println!("Please supply a path.");
+    println!("Please supply the path to a log file, or to the directory ACT writes logs to.");
     return;
   }
   // Get path to the file.
@@ -36,12 +37,29 @@ fn main() {
       }
     }
   } else { false };
-  // Create a log reader.
-  let reader = ActReader::new(path, stop);
-  let rx = reader.start().unwrap();
+  // Create a log reader, following the newest log if given a directory.
+  let reader = if Path::new(path).is_dir() {
+    ActReader::from_directory(path, stop)
+  } else {
+    ActReader::new(path, stop)
+  };
+  let rx = match reader.start() {
+    Ok(rx) => rx,
+    Err(e) => {
+      println!("Could not start reading: {}", e);
+      return;
+    }
+  };
   // Print out every entry.
-  loop {
-    let entry = rx.recv().unwrap();
+  for event in rx {
+    let entry = match event {
+      ReaderEvent::Item(entry) => entry,
+      ReaderEvent::Error(e) => {
+        println!("Error reading file: {}", e);
+        return;
+      },
+      _ => continue
+    };
     println!("{}", serde_json::to_string(&entry).unwrap());
   }
 }
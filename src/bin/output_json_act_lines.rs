@@ -0,0 +1,57 @@
+extern crate ffxiv_reader;
+extern crate serde_json;
+
+use ffxiv_reader::*;
+
+use std::env::args;
+use std::path::Path;
+
+// Reads an ACT network log, printing every line as JSON. Chat lines are printed as entries, the
+// other known line types with their fields parsed, and any other line with its raw fields.
+
+fn main() {
+  // Gather the arguments supplied to the program.
+  let args: Vec<String> = args().skip(1).collect();
+  // Ensure they are not empty.
+  if args.is_empty() {
+    println!("Please supply the path to a log file, or to the directory ACT writes logs to.");
+    return;
+  }
+  // Get path to the file.
+  let path = &args[0];
+  // Check whether the program should continue scanning the file or just stop.
+  let stop = if args.len() > 1 {
+    match args[1].to_lowercase().parse() {
+      Ok(b) => b,
+      Err(e) => {
+        println!("Invalid stop argument. Please specify true/false. {}", e);
+        return;
+      }
+    }
+  } else { false };
+  // Create a log reader, following the newest log if given a directory.
+  let reader = if Path::new(path).is_dir() {
+    ActReader::from_directory(path, stop)
+  } else {
+    ActReader::new(path, stop)
+  };
+  let rx = match reader.start_lines() {
+    Ok(rx) => rx,
+    Err(e) => {
+      println!("Could not start reading: {}", e);
+      return;
+    }
+  };
+  // Print out every line.
+  for event in rx {
+    let line = match event {
+      ReaderEvent::Item(line) => line,
+      ReaderEvent::Error(e) => {
+        println!("Error reading file: {}", e);
+        return;
+      },
+      _ => continue
+    };
+    println!("{}", serde_json::to_string(&line).unwrap());
+  }
+}
@@ -0,0 +1,55 @@
+extern crate ffxiv_reader;
+extern crate serde_json;
+
+use ffxiv_reader::*;
+
+use std::env::args;
+
+// Reads the chat log of every client given, printing each entry with the client it came from. A
+// process name reads every running process with that name, so several clients on the same machine
+// can be read at once.
+
+fn main() {
+  // Gather the arguments supplied to the program.
+  let args: Vec<String> = args().skip(1).collect();
+  // Ensure they are not empty.
+  if args.is_empty() {
+    println!("Please supply one or more PIDs or process names (e.g. ffxiv_dx11.exe).");
+    return;
+  }
+  // Collect the PIDs of every client, finding processes by name for arguments that aren't PIDs.
+  let mut pids = Vec::new();
+  for arg in &args {
+    match arg.parse() {
+      Ok(pid) => pids.push(pid),
+      Err(_) => {
+        let found = MultiClientReader::from_process_name(arg, false);
+        if found.pids().is_empty() {
+          println!("Could not find a process named {}.", arg);
+        }
+        pids.extend_from_slice(found.pids());
+      }
+    }
+  }
+  if pids.is_empty() {
+    return;
+  }
+  let mut reader = MultiClientReader::new(pids, false);
+  // Keep waiting for a client if it closes, logs out or disconnects.
+  reader.reconnect = true;
+  let rx = match reader.start() {
+    Ok(rx) => rx,
+    Err(e) => {
+      println!("Error reading memory: {}", e);
+      return;
+    }
+  };
+  // Print out every entry, and any errors from the clients.
+  for event in rx {
+    match event {
+      ReaderEvent::Item(tagged) => println!("{}", serde_json::to_string(&tagged).unwrap()),
+      ReaderEvent::Error(e) => println!("Error reading memory: {}", e),
+      _ => {}
+    }
+  }
+}
@@ -1,7 +1,7 @@
 extern crate ffxiv_reader;
 extern crate time;
 
-use ffxiv_reader::MemoryEntryReader;
+use ffxiv_reader::{MemoryEntryReader, OffsetProfile};
 use ffxiv_reader::messages::HasDisplayText;
 
 use std::env::args;
@@ -12,17 +12,9 @@ fn main() {
   let args: Vec<String> = args().skip(1).collect();
   // Ensure they are not empty.
   if args.is_empty() {
-    println!("Please supply a PID.");
+    println!("Please supply a PID or process name (e.g. ffxiv_dx11.exe).");
     return;
   }
-  // Attempt to parse a PID from the first arg.
-  let pid: u32 = match args[0].parse() {
-    Ok(p) => p,
-    Err(e) => {
-      println!("Invalid PID: {}.", e);
-      return;
-    }
-  };
   // Check whether the program should continue scanning memory or just stop.
   let stop = if args.len() > 1 {
     match args[1].to_lowercase().parse() {
@@ -33,10 +25,31 @@ fn main() {
       }
     }
   } else { false };
-  // Create a log reader.
-  let reader = MemoryEntryReader::new(pid, stop);
+  // Create a log reader, using the first arg as a PID or finding the process by name.
+  let mut reader = match args[0].parse() {
+    Ok(pid) => MemoryEntryReader::new(pid, stop),
+    Err(_) => match MemoryEntryReader::from_process_name(&args[0], stop) {
+      Some(r) => r,
+      None => {
+        println!("Could not find a process named {}.", args[0]);
+        return;
+      }
+    }
+  };
+  // Load an offset profile if one was specified, and use it whatever the game's version.
+  if args.len() > 2 {
+    reader.profile = match OffsetProfile::from_file(&args[2]) {
+      Ok(p) => p,
+      Err(e) => {
+        println!("Could not load offset profile {}: {}", args[2], e);
+        return;
+      }
+    };
+    reader.registry = None;
+  }
   // Print out every entry.
-  for entry in reader.iter() {
+  let mut entries = reader.iter();
+  for entry in &mut entries {
     let t = time::at(Timespec::new(entry.timestamp as i64, 0));
     let time_string = t.strftime("%d/%m/%Y %H:%M:%S").unwrap();
 
@@ -53,4 +66,7 @@ fn main() {
       sender,
       message);
   }
+  if let Some(e) = entries.error() {
+    println!("Error reading memory: {}", e);
+  }
 }
@@ -1,10 +1,11 @@
 extern crate ffxiv_reader;
 extern crate chrono;
 
-use ffxiv_reader::ActReader;
+use ffxiv_reader::{ActReader, ReaderEvent};
 use ffxiv_reader::messages::HasDisplayText;
 
 use std::env::args;
+use std::path::Path;
 use chrono::{Utc, TimeZone};
 
 fn main() {
@@ -12,7 +13,7 @@ fn main() {
   let args: Vec<String> = args().skip(1).collect();
   // Ensure they are not empty.
   if args.is_empty() {
-    println!("Please supply a path.");
+    println!("Please supply the path to a log file, or to the directory ACT writes logs to.");
     return;
   }
   // Get path to the file.
@@ -27,12 +28,29 @@ fn main() {
       }
     }
   } else { false };
-  // Create a log reader.
-  let reader = ActReader::new(path, stop);
-  let rx = reader.start().unwrap();
+  // Create a log reader, following the newest log if given a directory.
+  let reader = if Path::new(path).is_dir() {
+    ActReader::from_directory(path, stop)
+  } else {
+    ActReader::new(path, stop)
+  };
+  let rx = match reader.start() {
+    Ok(rx) => rx,
+    Err(e) => {
+      println!("Could not start reading: {}", e);
+      return;
+    }
+  };
   // Print out every entry.
-  loop {
-    let entry = rx.recv().unwrap();
+  for event in rx {
+    let entry = match event {
+      ReaderEvent::Item(entry) => entry,
+      ReaderEvent::Error(e) => {
+        println!("Error reading file: {}", e);
+        return;
+      },
+      _ => continue
+    };
 
     let timestamp = Utc.timestamp(entry.timestamp as i64, 0);
     let time_string = timestamp.format("%d/%m/%Y %H:%M:%S");
@@ -0,0 +1,59 @@
+extern crate ffxiv_reader;
+
+use ffxiv_reader::{ProcessMemory, ProfileRegistry};
+use ffxiv_reader::process::GAME_PROCESS_NAME;
+use ffxiv_reader::registry;
+
+use std::env::args;
+
+// Prints the version hash of a running game, for adding its offset profile to a registry. Also
+// prints which version of the given registry file matches, or of the built-in registry if none is
+// given and there is one.
+
+fn main() {
+  // Gather the arguments supplied to the program.
+  let args: Vec<String> = args().skip(1).collect();
+  // Ensure they are not empty.
+  if args.is_empty() {
+    println!("Please supply a PID or process name (e.g. ffxiv_dx11.exe).");
+    return;
+  }
+  // Use the first arg as a PID, or find the process by name if it isn't one.
+  let source = match args[0].parse() {
+    Ok(pid) => ProcessMemory::new(pid),
+    Err(_) => match ProcessMemory::from_name(&args[0]) {
+      Some(s) => s,
+      None => {
+        println!("Could not find a process named {}.", args[0]);
+        return;
+      }
+    }
+  };
+  // Load a registry if one was specified.
+  let registry = if args.len() > 1 {
+    match ProfileRegistry::from_file(&args[1]) {
+      Ok(r) => Some(r),
+      Err(e) => {
+        println!("Could not load profile registry {}: {}", args[1], e);
+        return;
+      }
+    }
+  } else {
+    ProfileRegistry::builtin()
+  };
+  let module = registry.as_ref().map(|r| &r.module[..]).unwrap_or(GAME_PROCESS_NAME);
+  let hash = match registry::game_version(&source, module) {
+    Ok(h) => h,
+    Err(e) => {
+      println!("Could not read the game version: {}", e);
+      return;
+    }
+  };
+  println!("{:016x}", hash);
+  if let Some(registry) = registry {
+    match registry.find(hash) {
+      Some(version) => println!("Matches version {}.", version.name),
+      None => println!("No version in the registry matches.")
+    }
+  }
+}
@@ -0,0 +1,35 @@
+extern crate ffxiv_reader;
+extern crate serde_json;
+
+use ffxiv_reader::MemoryEntryReader;
+use ffxiv_reader::snapshot::{Snapshot, SnapshotSource};
+
+use std::env::args;
+
+// Replays a snapshot captured by capture_snapshot through MemoryEntryReader, printing every entry
+// as JSON like output_json.
+
+fn main() {
+  // Gather the arguments supplied to the program.
+  let args: Vec<String> = args().skip(1).collect();
+  // Ensure they are not empty.
+  if args.is_empty() {
+    println!("Please supply a snapshot path.");
+    return;
+  }
+  let snapshot = match Snapshot::from_file(&args[0]) {
+    Ok(s) => s,
+    Err(e) => {
+      println!("Could not read snapshot {}: {}", args[0], e);
+      return;
+    }
+  };
+  // Create a log reader that stops at the end of the snapshot.
+  let profile = snapshot.profile.clone();
+  let mut reader = MemoryEntryReader::with_source(SnapshotSource::new(snapshot), true);
+  reader.profile = profile;
+  // Print out every entry.
+  for entry in reader.iter() {
+    println!("{}", serde_json::to_string(&entry).unwrap());
+  }
+}
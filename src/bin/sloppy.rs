@@ -15,17 +15,9 @@ fn main() {
   let args: Vec<String> = args().skip(1).collect();
   // Ensure they are not empty.
   if args.len() < 2 {
-    println!("Please supply a PID and device ID.");
+    println!("Please supply a PID or process name (e.g. ffxiv_dx11.exe) and device ID.");
     return;
   }
-  // Attempt to parse a PID from the first arg.
-  let pid: u32 = match args[0].parse() {
-    Ok(p) => p,
-    Err(e) => {
-      println!("Invalid PID: {}.", e);
-      return;
-    }
-  };
   // Attempt to parse a device ID from the second arg.
   let device_id: u32 = match args[1].parse() {
     Ok(i) => i,
@@ -44,14 +36,24 @@ fn main() {
       }
     }
   } else { false };
-  // Create a log reader.
-  let reader = MemoryEntryReader::new(pid, stop);
+  // Create a log reader, using the first arg as a PID or finding the process by name.
+  let reader = match args[0].parse() {
+    Ok(pid) => MemoryEntryReader::new(pid, stop),
+    Err(_) => match MemoryEntryReader::from_process_name(&args[0], stop) {
+      Some(r) => r,
+      None => {
+        println!("Could not find a process named {}.", args[0]);
+        return;
+      }
+    }
+  };
   // Record program start time, so it doesn't replay deaths.
   let start_time = time::now();
   // Last sloppy time
   let mut last_sloppy: Option<time::Tm> = None;
   // Loop over every old and new entry
-  for entry in reader.iter() {
+  let mut entries = reader.iter();
+  for entry in entries.by_ref() {
     // Skip anything that's not a death
     if entry.message_type != MessageType::BattleSystemMessages && entry.message_type != MessageType::BattleDeath {
       continue;
@@ -98,4 +100,8 @@ fn main() {
       .stderr(std::process::Stdio::null())
       .status();
   }
+  // Say why reading stopped, if it was an error.
+  if let Some(e) = entries.error() {
+    println!("Error reading memory: {}", e);
+  }
 }
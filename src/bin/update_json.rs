@@ -2,11 +2,10 @@ extern crate ffxiv_reader;
 extern crate serde_json;
 extern crate time;
 
-use ffxiv_reader::messages::entries::Entry;
+use ffxiv_reader::JsonEntryReader;
 use ffxiv_reader::messages::MessageType;
 use std::env::args;
-use std::fs::File;
-use std::io::Read;
+use std::process;
 
 fn main() {
   let args: Vec<String> = args().skip(1).collect();
@@ -15,25 +14,16 @@ fn main() {
     return;
   }
   let file_name = &args[0];
-  let mut file = match File::open(file_name) {
-    Ok(f) => f,
-    Err(e) => {
-      println!("Could not open {}: {}", file_name, e);
-      return;
-    }
-  };
-  let mut data = String::new();
-  if let Err(e) = file.read_to_string(&mut data) {
-    println!("Could not read {}: {}", file_name, e);
-    return;
-  }
-  let lines = data.split('\n').filter(|x| !x.is_empty());
-  let entries: Result<Vec<Entry>, serde_json::Error> = lines.map(serde_json::from_str).collect();
-  let entries = match entries {
+  // Read the whole file first, so nothing is written if any line isn't an entry.
+  let mut reader = JsonEntryReader::new(file_name, true);
+  reader.strict = true;
+  let entries = match reader.read_all() {
     Ok(e) => e,
-    Err(e) => {
-      println!("Could not parse JSON as entries: {}", e);
-      return;
+    Err(errors) => {
+      for e in errors {
+        println!("Could not read {}: {}", file_name, e);
+      }
+      process::exit(1);
     }
   };
   for mut entry in entries {
@@ -46,3 +36,4 @@ fn main() {
     println!("{}", serde_json::to_string(&entry).unwrap());
   }
 }
+
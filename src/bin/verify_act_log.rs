@@ -0,0 +1,50 @@
+extern crate ffxiv_reader;
+
+use ffxiv_reader::act::hash;
+
+use std::env::args;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::process;
+
+// Checks every line of an ACT network log against its hash, printing the lines that don't match.
+// Exits with a failure if any line doesn't match, so edited or corrupted logs can be caught by
+// scripts.
+
+fn main() {
+  // Gather the arguments supplied to the program.
+  let args: Vec<String> = args().skip(1).collect();
+  // Ensure they are not empty.
+  if args.is_empty() {
+    println!("Please supply the path to a network log.");
+    return;
+  }
+  let f = match File::open(&args[0]) {
+    Ok(f) => f,
+    Err(e) => {
+      println!("Could not open {}: {}", args[0], e);
+      process::exit(2);
+    }
+  };
+  let mut lines = 0;
+  let mut invalid = 0;
+  for (i, line) in BufReader::new(f).lines().enumerate() {
+    let line = match line {
+      Ok(l) => l,
+      Err(e) => {
+        println!("Error reading file: {}", e);
+        process::exit(2);
+      }
+    };
+    let number = i as u64 + 1;
+    lines += 1;
+    if !hash::verify_line(&line, number) {
+      invalid += 1;
+      println!("line {} doesn't match its hash: {}", number, line);
+    }
+  }
+  println!("{} of {} lines match their hashes.", lines - invalid, lines);
+  if invalid > 0 {
+    process::exit(1);
+  }
+}
@@ -0,0 +1,375 @@
+//! Channels for sending events from readers
+//!
+//! Readers send their events through a channel that can be bounded, so a slow consumer doesn't let
+//! events pile up in memory without limit. What happens when a bounded channel is full is decided
+//! by its [`OverflowPolicy`](enum.OverflowPolicy.html).
+//!
+//! Only items are ever dropped. Status events such as `Error` and `Detached` are always sent, even
+//! if the channel is full. When items are dropped, the receiver reports how many with a `Dropped`
+//! event in the place the items would have been.
+
+use events::ReaderEvent;
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{RecvError, RecvTimeoutError, SendError, TryRecvError};
+use std::time::{Duration, Instant};
+
+/// How often a send blocked on a full channel checks if its reader has been stopped.
+const STOP_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// What to do when sending an item to a full channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+  /// Wait until the receiver makes room. This holds up the reader until then, or until it is
+  /// stopped.
+  #[default]
+  Block,
+  /// Drop the oldest item in the channel to make room.
+  DropOldest,
+  /// Drop the item being sent.
+  DropNewest
+}
+
+struct State<T> {
+  /// Events waiting to be received, each with the number of items dropped right before it
+  queue: VecDeque<(u64, ReaderEvent<T>)>,
+  /// The number of items dropped after the last event in the queue
+  dropped: u64,
+  /// The number of items in the queue
+  items: usize,
+  /// The number of senders that haven't been dropped
+  senders: usize,
+  receiver_alive: bool
+}
+
+struct Shared<T> {
+  state: Mutex<State<T>>,
+  capacity: Option<usize>,
+  policy: OverflowPolicy,
+  /// Notified when an event is sent or the sender is dropped
+  sent: Condvar,
+  /// Notified when an event is received or the receiver is dropped
+  received: Condvar
+}
+
+impl<T> Shared<T> {
+  fn lock(&self) -> MutexGuard<State<T>> {
+    self.state.lock().unwrap_or_else(|e| e.into_inner())
+  }
+}
+
+/// Creates a channel for events.
+///
+/// `capacity` is the number of items the channel holds before `policy` applies. If it is `None`,
+/// the channel is unbounded.
+pub fn channel<T>(capacity: Option<usize>,
+                  policy: OverflowPolicy) -> (EventSender<T>, EventReceiver<T>) {
+  let shared = Arc::new(Shared {
+    state: Mutex::new(State {
+      queue: VecDeque::new(),
+      dropped: 0,
+      items: 0,
+      senders: 1,
+      receiver_alive: true
+    }),
+    capacity: capacity.map(|c| c.max(1)),
+    policy,
+    sent: Condvar::new(),
+    received: Condvar::new()
+  });
+  (EventSender { shared: shared.clone(), run: None }, EventReceiver { shared })
+}
+
+/// The sending half of a [`channel`](fn.channel.html).
+///
+/// The sender can be cloned to send events from several threads. The receiver is disconnected once
+/// every sender has been dropped.
+pub struct EventSender<T> {
+  shared: Arc<Shared<T>>,
+  /// If set, a send waiting for room gives up once this is `false`
+  run: Option<Arc<AtomicBool>>
+}
+
+impl<T> EventSender<T> {
+  /// Makes sends waiting for room in a full channel give up once `run` is `false`.
+  ///
+  /// Readers pass the flag cleared by their `stop` method, so a receiver that is no longer
+  /// receiving can't keep a stopped reader's thread waiting. Clones of this sender share the flag.
+  pub fn stop_with(&mut self, run: Arc<AtomicBool>) {
+    self.run = Some(run);
+  }
+
+  /// Sends an event, applying the channel's overflow policy if it is an item and the channel is
+  /// full.
+  ///
+  /// Fails if the receiver has been dropped, or if the sender was stopped while waiting for room.
+  pub fn send(&self, event: ReaderEvent<T>) -> Result<(), SendError<ReaderEvent<T>>> {
+    let shared = &*self.shared;
+    let mut state = shared.lock();
+    let is_item = event.is_item();
+    if let (true, Some(capacity)) = (is_item, shared.capacity) {
+      while state.receiver_alive && state.items >= capacity {
+        match shared.policy {
+          OverflowPolicy::Block => match self.run {
+            Some(ref run) => {
+              if !run.load(Ordering::Relaxed) {
+                return Err(SendError(event));
+              }
+              state = shared.received.wait_timeout(state, STOP_CHECK_INTERVAL)
+                .map(|(s, _)| s)
+                .unwrap_or_else(|e| e.into_inner().0);
+            },
+            None => state = shared.received.wait(state).unwrap_or_else(|e| e.into_inner())
+          },
+          OverflowPolicy::DropOldest => {
+            drop_oldest(&mut state);
+          },
+          OverflowPolicy::DropNewest => {
+            state.dropped += 1;
+            return Ok(());
+          }
+        }
+      }
+    }
+    if !state.receiver_alive {
+      return Err(SendError(event));
+    }
+    if is_item {
+      state.items += 1;
+    }
+    let dropped = state.dropped;
+    state.dropped = 0;
+    state.queue.push_back((dropped, event));
+    shared.sent.notify_one();
+    Ok(())
+  }
+
+  /// Checks if the receiver has been dropped, in which case nothing sent will be received.
+  pub fn is_disconnected(&self) -> bool {
+    !self.shared.lock().receiver_alive
+  }
+}
+
+/// Drops the oldest item in the queue, counting it before the event that followed it.
+fn drop_oldest<T>(state: &mut State<T>) {
+  let index = match state.queue.iter().position(|(_, e)| e.is_item()) {
+    Some(i) => i,
+    None => return
+  };
+  let (dropped, _) = state.queue.remove(index).unwrap();
+  state.items -= 1;
+  match state.queue.get_mut(index) {
+    Some(&mut (ref mut next, _)) => *next += dropped + 1,
+    None => state.dropped += dropped + 1
+  }
+}
+
+impl<T> Clone for EventSender<T> {
+  fn clone(&self) -> Self {
+    self.shared.lock().senders += 1;
+    EventSender { shared: self.shared.clone(), run: self.run.clone() }
+  }
+}
+
+impl<T> Drop for EventSender<T> {
+  fn drop(&mut self) {
+    self.shared.lock().senders -= 1;
+    self.shared.sent.notify_all();
+  }
+}
+
+/// The receiving half of a [`channel`](fn.channel.html).
+///
+/// This works like `std::sync::mpsc::Receiver`, and can be iterated over in the same way.
+pub struct EventReceiver<T> {
+  shared: Arc<Shared<T>>
+}
+
+impl<T> EventReceiver<T> {
+  /// Takes the next event from the queue, if there is one.
+  fn take(&self, state: &mut State<T>) -> Option<ReaderEvent<T>> {
+    if let Some(&mut (ref mut dropped, _)) = state.queue.front_mut() {
+      if *dropped > 0 {
+        let n = *dropped;
+        *dropped = 0;
+        return Some(ReaderEvent::Dropped(n));
+      }
+    }
+    if let Some((_, event)) = state.queue.pop_front() {
+      if event.is_item() {
+        state.items -= 1;
+      }
+      self.shared.received.notify_one();
+      return Some(event);
+    }
+    if state.dropped > 0 {
+      let n = state.dropped;
+      state.dropped = 0;
+      return Some(ReaderEvent::Dropped(n));
+    }
+    None
+  }
+
+  /// Waits for the next event.
+  ///
+  /// Fails once every sender has been dropped and every event has been received.
+  pub fn recv(&self) -> Result<ReaderEvent<T>, RecvError> {
+    let mut state = self.shared.lock();
+    loop {
+      if let Some(event) = self.take(&mut state) {
+        return Ok(event);
+      }
+      if state.senders == 0 {
+        return Err(RecvError);
+      }
+      state = self.shared.sent.wait(state).unwrap_or_else(|e| e.into_inner());
+    }
+  }
+
+  /// Gets the next event without waiting.
+  pub fn try_recv(&self) -> Result<ReaderEvent<T>, TryRecvError> {
+    let mut state = self.shared.lock();
+    match self.take(&mut state) {
+      Some(event) => Ok(event),
+      None if state.senders > 0 => Err(TryRecvError::Empty),
+      None => Err(TryRecvError::Disconnected)
+    }
+  }
+
+  /// Waits for the next event for at most `timeout`.
+  pub fn recv_timeout(&self, timeout: Duration) -> Result<ReaderEvent<T>, RecvTimeoutError> {
+    let deadline = Instant::now() + timeout;
+    let mut state = self.shared.lock();
+    loop {
+      if let Some(event) = self.take(&mut state) {
+        return Ok(event);
+      }
+      if state.senders == 0 {
+        return Err(RecvTimeoutError::Disconnected);
+      }
+      let now = Instant::now();
+      if now >= deadline {
+        return Err(RecvTimeoutError::Timeout);
+      }
+      state = self.shared.sent.wait_timeout(state, deadline - now)
+        .map(|(s, _)| s)
+        .unwrap_or_else(|e| e.into_inner().0);
+    }
+  }
+
+  /// Iterates over events, waiting for each one, until every sender is dropped.
+  pub fn iter(&self) -> Iter<T> {
+    Iter { rx: self }
+  }
+}
+
+impl<T> Drop for EventReceiver<T> {
+  fn drop(&mut self) {
+    let mut state = self.shared.lock();
+    state.receiver_alive = false;
+    state.queue.clear();
+    self.shared.received.notify_all();
+  }
+}
+
+/// An iterator over the events received by an [`EventReceiver`](struct.EventReceiver.html).
+pub struct Iter<'a, T: 'a> {
+  rx: &'a EventReceiver<T>
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+  type Item = ReaderEvent<T>;
+
+  fn next(&mut self) -> Option<ReaderEvent<T>> {
+    self.rx.recv().ok()
+  }
+}
+
+/// An owning iterator over the events received by an [`EventReceiver`](struct.EventReceiver.html).
+pub struct IntoIter<T> {
+  rx: EventReceiver<T>
+}
+
+impl<T> Iterator for IntoIter<T> {
+  type Item = ReaderEvent<T>;
+
+  fn next(&mut self) -> Option<ReaderEvent<T>> {
+    self.rx.recv().ok()
+  }
+}
+
+impl<'a, T> IntoIterator for &'a EventReceiver<T> {
+  type Item = ReaderEvent<T>;
+  type IntoIter = Iter<'a, T>;
+
+  fn into_iter(self) -> Iter<'a, T> {
+    self.iter()
+  }
+}
+
+impl<T> IntoIterator for EventReceiver<T> {
+  type Item = ReaderEvent<T>;
+  type IntoIter = IntoIter<T>;
+
+  fn into_iter(self) -> IntoIter<T> {
+    IntoIter { rx: self }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::thread;
+
+  #[test]
+  fn drops_items_by_policy() {
+    let (tx, rx) = channel(Some(2), OverflowPolicy::DropOldest);
+    for i in 0..4 {
+      tx.send(ReaderEvent::Item(i)).unwrap();
+    }
+    tx.send(ReaderEvent::Detached).unwrap();
+    match rx.try_recv() {
+      Ok(ReaderEvent::Dropped(2)) => {},
+      e => panic!("expected two dropped items, got {:?}", e)
+    }
+    assert_eq!(rx.try_recv().ok().and_then(ReaderEvent::item), Some(2));
+    assert_eq!(rx.try_recv().ok().and_then(ReaderEvent::item), Some(3));
+    assert!(rx.try_recv().map(|e| !e.is_item()).unwrap_or(false));
+  }
+
+  #[test]
+  fn blocked_sends_give_up_when_stopped() {
+    let run = Arc::new(AtomicBool::new(true));
+    let (mut tx, rx) = channel(Some(1), OverflowPolicy::Block);
+    tx.stop_with(run.clone());
+    tx.send(ReaderEvent::Item(0)).unwrap();
+    let sender = thread::spawn(move || tx.send(ReaderEvent::Item(1)).is_err());
+    thread::sleep(Duration::from_millis(50));
+    run.store(false, Ordering::Relaxed);
+    assert!(sender.join().unwrap());
+    assert_eq!(rx.try_recv().ok().and_then(ReaderEvent::item), Some(0));
+    assert!(rx.try_recv().is_err());
+  }
+
+  #[test]
+  fn reports_dropped_receivers() {
+    let (tx, rx) = channel::<u32>(None, OverflowPolicy::Block);
+    assert!(!tx.is_disconnected());
+    drop(rx);
+    assert!(tx.is_disconnected());
+  }
+
+  #[test]
+  fn blocked_sends_wait_for_room() {
+    let (tx, rx) = channel(Some(1), OverflowPolicy::Block);
+    tx.send(ReaderEvent::Item(0)).unwrap();
+    let sender = thread::spawn(move || tx.send(ReaderEvent::Item(1)).is_ok());
+    assert_eq!(rx.recv().ok().and_then(ReaderEvent::item), Some(0));
+    assert_eq!(rx.recv().ok().and_then(ReaderEvent::item), Some(1));
+    assert!(sender.join().unwrap());
+    assert!(rx.recv().is_err());
+  }
+}
@@ -0,0 +1,366 @@
+//! Tracking of the chat log in memory
+
+use memory::MemorySource;
+use profile::{OffsetProfile, Width};
+use registry::ProfileRegistry;
+use events::ReaderEvent;
+use error::ReaderError;
+
+use std::sync::Arc;
+
+/// The addresses of the chat log, resolved using an `OffsetProfile`.
+#[derive(Debug, Clone, Copy)]
+struct Addresses {
+  lines: usize,
+  index_pointer: usize,
+  chat_pointer: usize,
+  chat: usize
+}
+
+/// The number of lines the game keeps in the chat log before starting over.
+pub const LINES_PER_PAGE: u32 = 1000;
+
+/// The state of the main loop of a [`MemoryEntryReader`](../struct.MemoryEntryReader.html).
+///
+/// The game keeps a count of every line it has written to the chat log, a table of indices that
+/// point to where each line ends in the chat buffer, and a pointer to the end of that table.
+///
+/// The table and buffer hold at most 1000 lines, which we call a page. Once the count reaches a
+/// multiple of 1000, the game starts a new page, writing the table and buffer from the beginning
+/// again. The lines on the current page are the last `count % 1000` lines written.
+///
+/// Each poll compares the count to the number of lines that have already been read. New lines on
+/// the current page are read in order. If the page has turned over since the last poll, any lines
+/// left unread on the old pages have been overwritten, so a `Gap` is reported for them before the
+/// lines on the new page are read.
+pub struct ChatLog<S> {
+  source: Arc<S>,
+  profile: OffsetProfile,
+  /// If set, the profile is picked from this registry each time the chat log is attached
+  registry: Option<ProfileRegistry>,
+  addresses: Option<Addresses>,
+  /// The line count up to which lines have been read or reported lost
+  read_count: Option<u32>,
+  /// The last message read, used to avoid reading messages again after the chat log moves
+  last_message: Option<Vec<u8>>,
+  /// Whether the next poll has to skip past `last_message`
+  resync: bool,
+  /// Buffers reused between polls for the index table, the ends of the new lines and their text
+  index_buf: Vec<u8>,
+  ends: Vec<usize>,
+  text_buf: Vec<u8>
+}
+
+impl<S: MemorySource> ChatLog<S> {
+  pub fn new(source: Arc<S>, profile: OffsetProfile, registry: Option<ProfileRegistry>) -> Self {
+    ChatLog {
+      source,
+      profile,
+      registry,
+      addresses: None,
+      read_count: None,
+      last_message: None,
+      resync: false,
+      index_buf: Vec::new(),
+      ends: Vec::new(),
+      text_buf: Vec::new()
+    }
+  }
+
+  pub fn source(&self) -> &S {
+    &self.source
+  }
+
+  /// The profile in use, which may have been picked from the registry when attaching.
+  pub fn profile(&self) -> &OffsetProfile {
+    &self.profile
+  }
+
+  pub fn is_attached(&self) -> bool {
+    self.addresses.is_some()
+  }
+
+  /// Opens the source and resolves the addresses of the chat log.
+  pub fn attach(&mut self) -> Result<(), ReaderError> {
+    self.source.open().map_err(ReaderError::from_open)?;
+    // The game may have been patched since it was last attached to
+    if let Some(ref registry) = self.registry {
+      self.profile = registry.select(&*self.source)?.clone();
+    }
+    let chat_log = self.profile.resolve(&*self.source)?;
+    let chat_pointer = chat_log + self.profile.chat_pointer_offset;
+    let chat = read_int(&*self.source, self.profile.pointer_width, chat_pointer)?;
+    if chat == 0 {
+      return Err(ReaderError::InvalidPointerChain);
+    }
+    self.addresses = Some(Addresses {
+      lines: chat_log + self.profile.line_count_offset,
+      index_pointer: chat_log + self.profile.index_pointer_offset,
+      chat_pointer,
+      chat
+    });
+    Ok(())
+  }
+
+  /// Forgets the addresses of the chat log.
+  ///
+  /// Once reattached, messages will be read from the start of the current page, skipping any that
+  /// were already read.
+  pub fn detach(&mut self) {
+    self.addresses = None;
+    self.restart();
+  }
+
+  /// Reads any new messages, reporting any lines that were lost.
+  ///
+  /// Detaches if the memory can't be read. Polling while detached returns no events.
+  pub fn poll(&mut self) -> Result<Vec<ReaderEvent<Vec<u8>>>, ReaderError> {
+    let addresses = match self.addresses {
+      Some(a) => a,
+      None => return Ok(Vec::new())
+    };
+    self.source.next_poll();
+    let events = self.read_new(addresses);
+    if events.is_err() {
+      self.detach();
+    }
+    events
+  }
+
+  /// Starts reading from the start of the current page on the next poll.
+  fn restart(&mut self) {
+    self.read_count = None;
+    self.resync = self.last_message.is_some();
+  }
+
+  fn read_new(&mut self, addresses: Addresses) -> Result<Vec<ReaderEvent<Vec<u8>>>, ReaderError> {
+    let source = self.source.clone();
+    let source = &*source;
+    let pointer_width = self.profile.pointer_width;
+    let index_width = self.profile.index_width;
+    // If the chat log has moved, the game has reloaded it, so start from the beginning
+    let chat = read_int(source, pointer_width, addresses.chat_pointer)?;
+    if chat == 0 {
+      return Err(ReaderError::InvalidPointerChain);
+    }
+    if chat != addresses.chat {
+      self.addresses = Some(Addresses { chat, ..addresses });
+      self.restart();
+    }
+    // Read the total number of lines and work out where the current page starts
+    let count = read_int(source, Width::U32, addresses.lines)? as u32;
+    let page_lines = count % LINES_PER_PAGE;
+    let page_start = count - page_lines;
+    // If the count went backwards, the game has started a new chat log
+    if self.read_count.map(|r| r > count).unwrap_or(false) {
+      self.restart();
+    }
+    let mut events = Vec::new();
+    let read_count = match self.read_count {
+      Some(r) if r >= page_start => r,
+      Some(r) => {
+        // The page turned over before the rest of the old page was read
+        events.push(ReaderEvent::Gap(page_start - r));
+        page_start
+      },
+      None => page_start
+    };
+    if read_count == count {
+      self.read_count = Some(count);
+      return Ok(events);
+    }
+    // Read the indices of the new lines in one go, along with the one before them for where the
+    // first line starts
+    let pointer = read_int(source, pointer_width, addresses.index_pointer)?;
+    let size = index_width.size();
+    // A table that would start below address zero means the pointer isn't the one we expect
+    let table = pointer.checked_sub(size * page_lines as usize)
+      .ok_or(ReaderError::InvalidPointerChain)?;
+    let first = (read_count - page_start) as usize;
+    let table_start = if first == 0 { 0 } else { first - 1 };
+    let address = table + size * table_start;
+    self.index_buf.resize(size * (page_lines as usize - table_start), 0);
+    source.read_into(address, &mut self.index_buf)
+      .map_err(|e| ReaderError::from_read(e, address, self.index_buf.len()))?;
+    let mut indices = self.index_buf.chunks(size).map(|b| index_width.decode(b));
+    let start = if first == 0 { 0 } else { indices.next().unwrap_or(0) };
+    // A line that ends before the one before it hasn't been written yet, so stop there for now
+    let mut end = start;
+    self.ends.clear();
+    for index in indices {
+      if index < end {
+        break;
+      }
+      self.ends.push(index);
+      end = index;
+    }
+    // The new lines are next to each other in the chat buffer, so read all of their text at once
+    let address = chat + start;
+    self.text_buf.resize(end - start, 0);
+    source.read_into(address, &mut self.text_buf)
+      .map_err(|e| ReaderError::from_read(e, address, end - start))?;
+    let mut messages = Vec::with_capacity(self.ends.len());
+    let mut last_index = start;
+    for &index in &self.ends {
+      messages.push(self.text_buf[last_index - start..index - start].to_vec());
+      last_index = index;
+    }
+    self.read_count = Some(read_count + self.ends.len() as u32);
+    // After restarting, skip everything up to the last message read before
+    if self.resync {
+      self.resync = false;
+      let last_message = self.last_message.as_ref();
+      if let Some(i) = messages.iter().rposition(|m| Some(m) == last_message) {
+        messages.drain(..i + 1);
+      }
+    }
+    if let Some(last) = messages.last() {
+      self.last_message = Some(last.clone());
+    }
+    events.extend(messages.into_iter().map(ReaderEvent::Item));
+    Ok(events)
+  }
+}
+
+fn read_int<S: MemorySource>(source: &S,
+                             width: Width,
+                             address: usize) -> Result<usize, ReaderError> {
+  width.read(source, address).map_err(|e| ReaderError::from_read(e, address, width.size()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use memory::InMemorySource;
+
+  use byteorder::{ByteOrder, LittleEndian};
+
+  const BASE: usize = 0x1000_0000;
+  const LINES: usize = 0x2000_0000;
+  const INDEX: usize = 0x4000_0000;
+  const CHAT: usize = 0x5000_0000;
+
+  fn u32_bytes(value: usize) -> Vec<u8> {
+    let mut bytes = vec![0; 4];
+    LittleEndian::write_u32(&mut bytes, value as u32);
+    bytes
+  }
+
+  /// Creates a source with a pointer at the module base to the chat log at `LINES`.
+  fn setup() -> (Arc<InMemorySource>, OffsetProfile) {
+    let source = InMemorySource::new();
+    source.set_module("ffxiv_dx11.exe", BASE);
+    source.write(BASE, &u32_bytes(LINES));
+    source.write(LINES + 52, &u32_bytes(CHAT));
+    let profile = OffsetProfile { pointer_chain: vec![0, 0], ..Default::default() };
+    (Arc::new(source), profile)
+  }
+
+  /// Writes the current page of a chat log that has had `count` lines written to it in total.
+  ///
+  /// `page` holds the lines on the current page, so it should hold `count % 1000` lines.
+  fn write_page(source: &InMemorySource, count: usize, page: &[&str]) {
+    let mut index = Vec::new();
+    let mut chat = Vec::new();
+    for text in page {
+      chat.extend(text.as_bytes());
+      index.extend(u32_bytes(chat.len()));
+    }
+    source.write(INDEX, &index);
+    source.write(CHAT, &chat);
+    source.write(LINES, &u32_bytes(count));
+    source.write(LINES + 40, &u32_bytes(INDEX + index.len()));
+  }
+
+  /// Describes events as strings so they can be compared.
+  fn describe(events: Vec<ReaderEvent<Vec<u8>>>) -> Vec<String> {
+    events.into_iter()
+      .map(|e| match e {
+        ReaderEvent::Item(bytes) => String::from_utf8(bytes).unwrap(),
+        ReaderEvent::Gap(n) => format!("gap {}", n),
+        e => format!("{:?}", e)
+      })
+      .collect()
+  }
+
+  fn attached(source: &Arc<InMemorySource>, profile: &OffsetProfile) -> ChatLog<InMemorySource> {
+    let mut chat_log = ChatLog::new(source.clone(), profile.clone(), None);
+    chat_log.attach().unwrap();
+    chat_log
+  }
+
+  #[test]
+  fn reads_new_lines() {
+    let (source, profile) = setup();
+    write_page(&source, 2, &["a", "b"]);
+    let mut chat_log = attached(&source, &profile);
+    assert_eq!(describe(chat_log.poll().unwrap()), vec!["a", "b"]);
+    assert!(chat_log.poll().unwrap().is_empty());
+    write_page(&source, 4, &["a", "b", "cc", "d"]);
+    assert_eq!(describe(chat_log.poll().unwrap()), vec!["cc", "d"]);
+  }
+
+  #[test]
+  fn reports_gaps_when_pages_turn_over() {
+    let (source, profile) = setup();
+    write_page(&source, 3, &["a", "b", "c"]);
+    let mut chat_log = attached(&source, &profile);
+    assert_eq!(chat_log.poll().unwrap().len(), 3);
+    // Lines 3 to 999 were written and overwritten between polls
+    write_page(&source, 1002, &["x", "y"]);
+    assert_eq!(describe(chat_log.poll().unwrap()), vec!["gap 997", "x", "y"]);
+    // A page that turns over with nothing on the new page yet
+    write_page(&source, 2000, &[]);
+    assert_eq!(describe(chat_log.poll().unwrap()), vec!["gap 998"]);
+    write_page(&source, 2001, &["z"]);
+    assert_eq!(describe(chat_log.poll().unwrap()), vec!["z"]);
+    // Several pages turning over at once
+    write_page(&source, 5001, &["w"]);
+    assert_eq!(describe(chat_log.poll().unwrap()), vec!["gap 2999", "w"]);
+  }
+
+  #[test]
+  fn stops_at_lines_not_written_yet() {
+    let (source, profile) = setup();
+    write_page(&source, 2, &["a", "b"]);
+    // The count and table were updated before the end of the third line was
+    source.write(LINES, &u32_bytes(3));
+    source.write(INDEX + 8, &u32_bytes(0));
+    source.write(LINES + 40, &u32_bytes(INDEX + 12));
+    let mut chat_log = attached(&source, &profile);
+    assert_eq!(describe(chat_log.poll().unwrap()), vec!["a", "b"]);
+    write_page(&source, 3, &["a", "b", "c"]);
+    assert_eq!(describe(chat_log.poll().unwrap()), vec!["c"]);
+  }
+
+  #[test]
+  fn resyncs_after_detaching() {
+    let (source, profile) = setup();
+    write_page(&source, 3, &["a", "b", "c"]);
+    let mut chat_log = attached(&source, &profile);
+    assert_eq!(chat_log.poll().unwrap().len(), 3);
+    chat_log.detach();
+    assert!(chat_log.poll().unwrap().is_empty());
+    write_page(&source, 5, &["a", "b", "c", "d", "e"]);
+    chat_log.attach().unwrap();
+    assert_eq!(describe(chat_log.poll().unwrap()), vec!["d", "e"]);
+    // The game started a new chat log, without the last message read
+    chat_log.detach();
+    write_page(&source, 2, &["f", "g"]);
+    chat_log.attach().unwrap();
+    assert_eq!(describe(chat_log.poll().unwrap()), vec!["f", "g"]);
+  }
+
+  #[test]
+  fn rejects_index_pointers_below_the_table() {
+    let (source, profile) = setup();
+    write_page(&source, 3, &["a", "b", "c"]);
+    source.write(LINES + 40, &u32_bytes(4));
+    let mut chat_log = attached(&source, &profile);
+    match chat_log.poll() {
+      Err(ReaderError::InvalidPointerChain) => {},
+      r => panic!("expected an invalid pointer chain, got {:?}", r)
+    }
+    assert!(!chat_log.is_attached());
+  }
+}
@@ -0,0 +1,260 @@
+//! Checkpoints for resuming readers without delivering entries again
+//!
+//! A reader with a checkpoint file loads it when started and only delivers entries that come after
+//! it. As entries are delivered, the checkpoint is updated and saved back to the file.
+//!
+//! The game's chat log has no stable position to resume from, since the buffer is rewritten every
+//! 1000 lines and cleared when the game restarts. A memory checkpoint is instead the timestamp of
+//! the last entry delivered and hashes of every entry delivered with that timestamp. ACT logs only
+//! grow, so an ACT checkpoint is the byte offset of the end of the last line read, along with the
+//! log it is in.
+
+use byteorder::{ByteOrder, LittleEndian};
+use serde_json;
+
+use fnv;
+
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// A position to resume reading from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Checkpoint {
+  /// A position in the game's chat log.
+  Memory(MemoryCheckpoint),
+  /// A position in an ACT log.
+  Act(ActCheckpoint)
+}
+
+impl Checkpoint {
+  /// Reads a checkpoint from a file.
+  pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+    serde_json::from_reader(BufReader::new(File::open(path)?))
+      .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+  }
+
+  /// Reads a checkpoint from a file, if the file exists.
+  pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Option<Self>> {
+    match Checkpoint::from_file(path) {
+      Ok(c) => Ok(Some(c)),
+      Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+      Err(e) => Err(e)
+    }
+  }
+
+  /// Writes this checkpoint to a file.
+  ///
+  /// The checkpoint is written to a temporary file next to `path` first, then moved into place, so
+  /// the file never holds a partial checkpoint.
+  pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+    let path = path.as_ref();
+    let mut temp_name = path.file_name().map(|n| n.to_os_string()).unwrap_or_default();
+    temp_name.push(".tmp");
+    let temp = path.with_file_name(temp_name);
+    let json = serde_json::to_vec(self)
+      .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    File::create(&temp)?.write_all(&json)?;
+    fs::rename(&temp, path)
+  }
+}
+
+/// A position in the game's chat log.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MemoryCheckpoint {
+  /// The timestamp of the last entry delivered.
+  pub timestamp: u32,
+  /// Hashes of the raw bytes of the entries delivered with the last timestamp, oldest first.
+  ///
+  /// See [`hash_entry`](fn.hash_entry.html).
+  pub hashes: Vec<u64>
+}
+
+impl MemoryCheckpoint {
+  /// Reads a memory checkpoint from a file.
+  ///
+  /// If the file doesn't exist, the checkpoint is empty. It is an error if the file holds a
+  /// different kind of checkpoint.
+  pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+    match Checkpoint::load(path)? {
+      Some(Checkpoint::Memory(c)) => Ok(c),
+      Some(_) => Err(io::Error::new(io::ErrorKind::InvalidData, "not a memory checkpoint")),
+      None => Ok(MemoryCheckpoint::default())
+    }
+  }
+
+  /// Updates the checkpoint with an entry that was delivered.
+  ///
+  /// Entries without a timestamp, or older than the checkpoint, are skipped when resuming whether
+  /// they are recorded or not, so they aren't.
+  pub fn record(&mut self, bytes: &[u8]) {
+    let timestamp = opt_or!(entry_timestamp(bytes), return);
+    if timestamp > self.timestamp {
+      self.timestamp = timestamp;
+      self.hashes.clear();
+    }
+    if timestamp == self.timestamp {
+      self.hashes.push(hash_entry(bytes));
+    }
+  }
+
+  /// Creates a filter that skips the entries this checkpoint has seen.
+  pub fn resume(&self) -> ResumeFilter {
+    ResumeFilter {
+      timestamp: self.timestamp,
+      hashes: self.hashes.iter().cloned().collect(),
+      resumed: false
+    }
+  }
+}
+
+/// Skips entries that were delivered before a [`MemoryCheckpoint`](struct.MemoryCheckpoint.html).
+///
+/// Entries older than the checkpoint are skipped. Entries from the same second are skipped if their
+/// hash is one of the checkpoint's, each hash skipping one entry. Once a newer or unseen entry is
+/// found, every entry after it is new.
+#[derive(Debug, Clone)]
+pub struct ResumeFilter {
+  timestamp: u32,
+  hashes: VecDeque<u64>,
+  resumed: bool
+}
+
+impl ResumeFilter {
+  /// Checks if the entry with the raw bytes `bytes` is new, given the entries checked before it.
+  pub fn is_new(&mut self, bytes: &[u8]) -> bool {
+    if self.resumed {
+      return true;
+    }
+    let timestamp = match entry_timestamp(bytes) {
+      Some(t) => t,
+      None => return false
+    };
+    if timestamp < self.timestamp {
+      return false;
+    }
+    let hash = hash_entry(bytes);
+    if timestamp == self.timestamp {
+      if let Some(i) = self.hashes.iter().position(|h| *h == hash) {
+        self.hashes.remove(i);
+        return false;
+      }
+    }
+    self.resumed = true;
+    true
+  }
+}
+
+/// A position in an ACT log.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ActCheckpoint {
+  /// The byte offset just past the last line read.
+  pub offset: u64,
+  /// The log the offset is in.
+  ///
+  /// This is used by readers following a directory of logs to resume from the right one.
+  #[serde(default)]
+  pub file: Option<PathBuf>
+}
+
+impl ActCheckpoint {
+  /// Reads an ACT checkpoint from a file.
+  ///
+  /// If the file doesn't exist, the checkpoint is at the start of the log. It is an error if the
+  /// file holds a different kind of checkpoint.
+  pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+    Ok(ActCheckpoint::load_saved(path)?.unwrap_or_default())
+  }
+
+  /// Reads an ACT checkpoint from a file, if the file exists.
+  ///
+  /// It is an error if the file holds a different kind of checkpoint.
+  pub fn load_saved<P: AsRef<Path>>(path: P) -> io::Result<Option<Self>> {
+    match Checkpoint::load(path)? {
+      Some(Checkpoint::Act(c)) => Ok(Some(c)),
+      Some(_) => Err(io::Error::new(io::ErrorKind::InvalidData, "not an ACT checkpoint")),
+      None => Ok(None)
+    }
+  }
+}
+
+/// Hashes the raw bytes of an entry.
+///
+/// This uses 64-bit FNV-1a, so hashes stay the same across versions of Rust and of this crate.
+pub fn hash_entry(bytes: &[u8]) -> u64 {
+  fnv::hash(bytes)
+}
+
+fn entry_timestamp(bytes: &[u8]) -> Option<u32> {
+  if bytes.len() < 4 {
+    return None;
+  }
+  Some(LittleEndian::read_u32(&bytes[..4]))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use testing::line;
+
+  use std::env;
+
+  /// Checks which of `entries` a filter for `checkpoint` finds to be new.
+  fn new_entries(checkpoint: &MemoryCheckpoint, entries: &[Vec<u8>]) -> Vec<bool> {
+    let mut filter = checkpoint.resume();
+    entries.iter().map(|e| filter.is_new(e)).collect()
+  }
+
+  #[test]
+  fn skips_delivered_entries() {
+    let delivered = [line(100, "A", "one"), line(101, "A", "two"), line(101, "A", "three")];
+    let mut checkpoint = MemoryCheckpoint::default();
+    for entry in &delivered {
+      checkpoint.record(entry);
+    }
+    assert_eq!(checkpoint.timestamp, 101);
+    assert_eq!(checkpoint.hashes.len(), 2);
+    let mut entries = delivered.to_vec();
+    entries.push(line(101, "A", "four"));
+    entries.push(line(102, "A", "five"));
+    assert_eq!(new_entries(&checkpoint, &entries), vec![false, false, false, true, true]);
+    // The same entry twice in one second is delivered again only as many times as it was seen
+    checkpoint.record(&line(102, "A", "again"));
+    let entries = [line(102, "A", "again"), line(102, "A", "again")];
+    assert_eq!(new_entries(&checkpoint, &entries), vec![false, true]);
+  }
+
+  #[test]
+  fn remembers_every_entry_of_the_last_second() {
+    let delivered: Vec<_> = (0..100).map(|i| line(100, "A", &format!("hit {}", i))).collect();
+    let mut checkpoint = MemoryCheckpoint::default();
+    for entry in &delivered {
+      checkpoint.record(entry);
+    }
+    let mut entries = delivered.clone();
+    entries.push(line(100, "A", "hit 100"));
+    let new = new_entries(&checkpoint, &entries);
+    assert_eq!(new.iter().filter(|n| **n).count(), 1);
+    assert!(new[100]);
+  }
+
+  #[test]
+  fn saves_and_loads_checkpoints() {
+    let name = format!("ffxiv_reader_checkpoint_{}.json", ::std::process::id());
+    let path = env::temp_dir().join(name);
+    assert_eq!(MemoryCheckpoint::load(&path).unwrap(), MemoryCheckpoint::default());
+    assert_eq!(ActCheckpoint::load_saved(&path).unwrap(), None);
+    let mut memory = MemoryCheckpoint::default();
+    memory.record(&line(100, "A", "one"));
+    Checkpoint::Memory(memory.clone()).save(&path).unwrap();
+    assert_eq!(MemoryCheckpoint::load(&path).unwrap(), memory);
+    assert_eq!(ActCheckpoint::load(&path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    let act = ActCheckpoint { offset: 42, file: Some(PathBuf::from("Network_1.log")) };
+    Checkpoint::Act(act.clone()).save(&path).unwrap();
+    assert_eq!(ActCheckpoint::load(&path).unwrap(), act);
+    assert_eq!(MemoryCheckpoint::load(&path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    fs::remove_file(&path).unwrap();
+  }
+}
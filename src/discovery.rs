@@ -0,0 +1,641 @@
+//! Finding the chat log again after a game patch
+//!
+//! When a patch moves the chat log, the offsets in an
+//! [`OffsetProfile`](../profile/struct.OffsetProfile.html) have to be found again. Given a dump of
+//! the game's memory and the message of a line that was just sent in chat,
+//! [`discover`](fn.discover.html) finds them in four steps:
+//!
+//! 1. The line is found in the chat buffer by its message, which follows the `:` after the sender.
+//! 2. The index table is found by looking for two neighbouring indices whose difference is the
+//!    length of the line. The indices are relative to the start of the chat buffer, so this also
+//!    gives its address.
+//! 3. The chat log structure is found by looking for a pointer to the chat buffer, with a pointer
+//!    to the end of the index table and a line count that agrees with it nearby.
+//! 4. Pointer chains from the game's module to the structure are found by searching backwards for
+//!    pointers to it, then for pointers to those, and so on.
+//!
+//! Every chain that resolves to the structure is returned as a profile, shortest first. The line
+//! must not be the first line of a page, and should be the last line written when the dump was
+//! taken.
+
+use memory::{MemorySource, ProcessMemory};
+use error::ReaderError;
+use process;
+use profile::{OffsetProfile, Width};
+use signature;
+use snapshot::{Snapshot, SnapshotWriter};
+use chat_log::LINES_PER_PAGE;
+
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The size of the chunks process memory is read in. Chunks that can't be read are left out.
+const CHUNK_SIZE: usize = 0x100000;
+/// The most bytes before a line's message that are searched for the `:` before its sender.
+const MAX_SENDER_LENGTH: usize = 64;
+/// The most bytes apart the fields of the chat log structure can be.
+const MAX_STRUCTURE_SIZE: usize = 0x200;
+/// The size assumed for the game's module if its headers can't be read.
+const DEFAULT_MODULE_SIZE: usize = 0x400_0000;
+/// The most pointers followed at each step of the search for pointer chains.
+const MAX_CHAIN_NODES: usize = 4096;
+/// The range of timestamps a line can have, from 2010 until they no longer fit in an `i32`.
+const MIN_TIMESTAMP: usize = 1_262_304_000;
+const MAX_TIMESTAMP: usize = 0x7fff_ffff;
+
+/// The readable memory of the game, to search for the chat log.
+///
+/// A dump of a running game reads its memory from the process one region at a time as it is
+/// searched, so the whole of the game's memory is never held at once. The game should be left alone
+/// while it is searched.
+pub struct MemoryDump {
+  /// The name of the game's module.
+  pub module: String,
+  /// The base address of the game's module.
+  pub module_base: usize,
+  memory: DumpMemory
+}
+
+enum DumpMemory {
+  /// Regions of memory that were copied, as addresses and the bytes at them.
+  Copied(Vec<(usize, Vec<u8>)>),
+  /// A process and the ranges of its memory that can be read.
+  Process(ProcessMemory, Vec<(usize, usize)>)
+}
+
+impl MemoryDump {
+  /// Uses the readable memory of the process using PID `pid`.
+  ///
+  /// Memory that can't be read is left out. Fails if the process can't be read or the module
+  /// named `module` isn't loaded in it.
+  pub fn from_process(pid: u32, module: &str) -> Result<Self, ReaderError> {
+    let source = ProcessMemory::new(pid);
+    source.open().map_err(ReaderError::from_open)?;
+    let module_base = match source.base_address(module) {
+      Some(b) => b,
+      None => return Err(ReaderError::ModuleNotFound(module.to_owned()))
+    };
+    let ranges = process::readable_regions(pid)?;
+    Ok(MemoryDump {
+      module: module.to_owned(),
+      module_base,
+      memory: DumpMemory::Process(source, ranges)
+    })
+  }
+
+  /// Uses regions of memory already copied, as addresses and the bytes at them.
+  pub fn from_regions(module: &str, module_base: usize, regions: Vec<(usize, Vec<u8>)>) -> Self {
+    MemoryDump {
+      module: module.to_owned(),
+      module_base,
+      memory: DumpMemory::Copied(regions)
+    }
+  }
+
+  /// Uses the memory in the last frame of a snapshot.
+  ///
+  /// The module is the one in the snapshot's profile. Only snapshots saved with
+  /// [`save`](#method.save) hold the memory of the module, which is needed to find pointer chains;
+  /// see [`has_module_memory`](#method.has_module_memory).
+  pub fn from_snapshot(snapshot: Snapshot) -> Self {
+    let regions = snapshot.frames.into_iter().last().map(|f| f.regions).unwrap_or_default();
+    MemoryDump::from_regions(&snapshot.profile.module, snapshot.module_base, regions)
+  }
+
+  /// Copies all of the memory of the dump, so it no longer changes with the process.
+  pub fn copy(&self) -> Self {
+    let mut regions = Vec::new();
+    self.for_each_region(|start, data| regions.push((start, data.to_vec())));
+    MemoryDump::from_regions(&self.module, self.module_base, regions)
+  }
+
+  /// Writes the dump as a snapshot with one frame, so it can be searched again later.
+  ///
+  /// A dump of a running game is copied first, so all of its memory is held at once.
+  pub fn save<W: Write>(&self, writer: W) -> io::Result<()> {
+    let regions = match self.memory {
+      DumpMemory::Copied(ref regions) => regions,
+      DumpMemory::Process(..) => return self.copy().save(writer)
+    };
+    let profile = OffsetProfile {
+      module: self.module.clone(),
+      ..OffsetProfile::default()
+    };
+    let mut writer = SnapshotWriter::new(writer, &profile, self.module_base)?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let timestamp = now.as_secs() * 1000 + now.subsec_nanos() as u64 / 1_000_000;
+    writer.write_regions(timestamp, 0, 0, regions)
+  }
+
+  /// Checks if the dump holds the start of the game's module.
+  ///
+  /// Pointer chains can only be found from the module's memory. Snapshots recorded by
+  /// `capture_snapshot` only hold the chat log, so they don't have it.
+  pub fn has_module_memory(&self) -> bool {
+    self.contains(self.module_base)
+  }
+
+  /// Calls `f` with the address and bytes of each region of memory, one at a time.
+  ///
+  /// The memory of a process is read in chunks, and chunks that can be read next to each other
+  /// are joined back into one region. Chunks that can't be read are left out.
+  fn for_each_region<F: FnMut(usize, &[u8])>(&self, mut f: F) {
+    let (source, ranges) = match self.memory {
+      DumpMemory::Copied(ref regions) => {
+        for &(start, ref data) in regions {
+          f(start, data);
+        }
+        return;
+      },
+      DumpMemory::Process(ref source, ref ranges) => (source, ranges)
+    };
+    for &(start, end) in ranges {
+      let mut region_start = start;
+      let mut data = Vec::new();
+      let mut address = start;
+      while address < end {
+        let size = (end - address).min(CHUNK_SIZE);
+        match source.read(address, size) {
+          Ok(bytes) => data.extend_from_slice(&bytes),
+          Err(_) => {
+            if !data.is_empty() {
+              f(region_start, &data);
+              data.clear();
+            }
+            region_start = address + size;
+          }
+        }
+        address += size;
+      }
+      if !data.is_empty() {
+        f(region_start, &data);
+      }
+    }
+  }
+
+  /// The end of the highest range of memory in the dump.
+  fn end(&self) -> usize {
+    match self.memory {
+      DumpMemory::Copied(ref regions) => {
+        regions.iter().map(|&(start, ref data)| start + data.len()).max().unwrap_or(0)
+      },
+      DumpMemory::Process(_, ref ranges) => ranges.iter().map(|&(_, end)| end).max().unwrap_or(0)
+    }
+  }
+
+  /// Finds the range of memory holding `address`, as its start and end.
+  fn range(&self, address: usize) -> Option<(usize, usize)> {
+    let in_range = |start: usize, end: usize| address >= start && address < end;
+    match self.memory {
+      DumpMemory::Copied(ref regions) => regions.iter()
+        .map(|&(start, ref data)| (start, start + data.len()))
+        .find(|&(start, end)| in_range(start, end)),
+      DumpMemory::Process(_, ref ranges) => ranges.iter()
+        .cloned()
+        .find(|&(start, end)| in_range(start, end))
+    }
+  }
+
+  /// Checks if the byte at `address` is in the dump.
+  fn contains(&self, address: usize) -> bool {
+    self.range(address).is_some()
+  }
+
+  /// Gets the memory within `radius` bytes of `address`, and the address it starts at.
+  fn around(&self, address: usize, radius: usize) -> Option<(usize, Vec<u8>)> {
+    let (start, end) = opt!(self.range(address));
+    let from = address.saturating_sub(radius).max(start);
+    let to = address.saturating_add(radius).min(end);
+    Some((from, opt!(self.read(from, to - from).ok())))
+  }
+
+  /// Calls `f` with the address and value of every aligned integer of width `width`.
+  fn scan<F: FnMut(usize, usize)>(&self, width: Width, mut f: F) {
+    let size = width.size();
+    self.for_each_region(|start, data| {
+      let mut offset = (size - start % size) % size;
+      while offset + size <= data.len() {
+        f(start + offset, width.decode(&data[offset..]));
+        offset += size;
+      }
+    });
+  }
+}
+
+impl MemorySource for MemoryDump {
+  fn read_into(&self, address: usize, buf: &mut [u8]) -> io::Result<()> {
+    let regions = match self.memory {
+      DumpMemory::Copied(ref regions) => regions,
+      DumpMemory::Process(ref source, _) => return source.read_into(address, buf)
+    };
+    for &(start, ref data) in regions {
+      if address >= start && address + buf.len() <= start + data.len() {
+        buf.copy_from_slice(&data[address - start..address - start + buf.len()]);
+        return Ok(());
+      }
+    }
+    Err(io::Error::new(io::ErrorKind::UnexpectedEof, format!("no memory at {:#x}", address)))
+  }
+
+  fn base_address(&self, module: &str) -> Option<usize> {
+    if module == self.module {
+      Some(self.module_base)
+    } else {
+      None
+    }
+  }
+}
+
+/// How to search for the chat log.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscoveryOptions {
+  /// The width of pointers in the game's memory.
+  ///
+  /// If this is `None`, pointers are 64 bits wide if any memory was copied from above 4 GiB.
+  pub pointer_width: Option<Width>,
+  /// The width of each entry in the index table.
+  pub index_width: Width,
+  /// The most pointers to follow from the module to the chat log structure.
+  pub max_depth: usize,
+  /// The largest offset to add to a pointer in a chain.
+  pub max_offset: usize
+}
+
+impl Default for DiscoveryOptions {
+  fn default() -> Self {
+    DiscoveryOptions {
+      pointer_width: None,
+      index_width: Width::U32,
+      max_depth: 4,
+      max_offset: 0x1000
+    }
+  }
+}
+
+/// A line found in the chat buffer, as the addresses of its start and end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FoundLine {
+  pub start: usize,
+  pub end: usize
+}
+
+/// The chat buffer and index table found from a line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FoundTable {
+  /// The address of the start of the chat buffer.
+  pub chat: usize,
+  /// The address of the line's index in the index table.
+  pub index: usize
+}
+
+/// A chat log structure, as its address and the offsets of its fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FoundStructure {
+  pub address: usize,
+  pub line_count_offset: usize,
+  pub index_pointer_offset: usize,
+  pub chat_pointer_offset: usize
+}
+
+/// Finds profiles that lead to the chat log holding a line with the message `text`.
+///
+/// See the [module documentation](index.html) for how. The profiles are sorted with the shortest
+/// pointer chains first.
+pub fn discover(dump: &MemoryDump, text: &str, options: &DiscoveryOptions) -> Vec<OffsetProfile> {
+  let pointer_width = options.pointer_width.unwrap_or_else(|| guess_pointer_width(dump));
+  let index_width = options.index_width;
+  let mut structures = HashSet::new();
+  for line in find_lines(dump, text) {
+    for table in find_tables(dump, line, index_width) {
+      structures.extend(find_structures(dump, table, pointer_width, index_width));
+    }
+  }
+  let mut profiles = Vec::new();
+  for structure in structures {
+    let chains = find_pointer_chains(dump,
+                                     structure.address,
+                                     pointer_width,
+                                     options.max_depth,
+                                     options.max_offset);
+    for chain in chains {
+      let profile = OffsetProfile {
+        module: dump.module.clone(),
+        pointer_chain: chain,
+        line_count_offset: structure.line_count_offset,
+        index_pointer_offset: structure.index_pointer_offset,
+        chat_pointer_offset: structure.chat_pointer_offset,
+        pointer_width,
+        index_width,
+        signature: None,
+        character_name: None,
+        home_world: None
+      };
+      if profile.resolve(dump).ok() == Some(structure.address) && !profiles.contains(&profile) {
+        profiles.push(profile);
+      }
+    }
+  }
+  profiles.sort_by_key(|p| (p.pointer_chain.len(), p.pointer_chain.iter().skip(1).sum::<usize>()));
+  profiles
+}
+
+fn guess_pointer_width(dump: &MemoryDump) -> Width {
+  if dump.end() > 0x1_0000_0000 {
+    Width::U64
+  } else {
+    Width::U32
+  }
+}
+
+/// Finds the lines in the chat buffer with the message `text`.
+///
+/// A line is a header holding its timestamp, followed by `:`, the sender, `:` and the message.
+pub fn find_lines(dump: &MemoryDump, text: &str) -> Vec<FoundLine> {
+  let text = text.as_bytes();
+  let mut lines = Vec::new();
+  if text.is_empty() {
+    return lines;
+  }
+  dump.for_each_region(|start, data| {
+    for message in find_all(data, text) {
+      if message < 2 || data[message - 1] != b':' {
+        continue;
+      }
+      let search_start = message.saturating_sub(MAX_SENDER_LENGTH + 1);
+      let colon = match data[search_start..message - 1].iter().rposition(|b| *b == b':') {
+        Some(i) => search_start + i,
+        None => continue
+      };
+      if colon < 8 || !is_timestamp(Width::U32.decode(&data[colon - 8..])) {
+        continue;
+      }
+      lines.push(FoundLine {
+        start: start + colon - 8,
+        end: start + message + text.len()
+      });
+    }
+  });
+  lines
+}
+
+/// Finds the positions of `needle` in `haystack`, which must not be empty.
+fn find_all(haystack: &[u8], needle: &[u8]) -> Vec<usize> {
+  let mut found = Vec::new();
+  let mut from = 0;
+  while from + needle.len() <= haystack.len() {
+    // Skip straight to the next byte that could start a match
+    match haystack[from..haystack.len() - needle.len() + 1].iter().position(|b| *b == needle[0]) {
+      Some(i) => from += i,
+      None => break
+    }
+    if &haystack[from..from + needle.len()] == needle {
+      found.push(from);
+    }
+    from += 1;
+  }
+  found
+}
+
+fn is_timestamp(value: usize) -> bool {
+  (MIN_TIMESTAMP..=MAX_TIMESTAMP).contains(&value)
+}
+
+/// Finds index tables that have an index for `line`.
+///
+/// The index before the line's is where the line starts, and the line's index is where it ends,
+/// both relative to the start of the chat buffer.
+pub fn find_tables(dump: &MemoryDump, line: FoundLine, index_width: Width) -> Vec<FoundTable> {
+  let length = line.end - line.start;
+  let size = index_width.size();
+  let mut tables = Vec::new();
+  let mut previous = None;
+  dump.scan(index_width, |address, index| {
+    if let Some((previous_address, start)) = previous {
+      let is_pair = previous_address + size == address
+        && index > start
+        && index - start == length
+        && start <= line.start;
+      if is_pair && dump.contains(line.start - start) {
+        tables.push(FoundTable {
+          chat: line.start - start,
+          index: address
+        });
+      }
+    }
+    previous = Some((address, index));
+  });
+  tables
+}
+
+/// Finds chat log structures that point to `table`.
+///
+/// The structure holds a pointer to the chat buffer, a pointer to the end of the index table and
+/// the total number of lines written. The number of lines on the page must agree with the end of
+/// the table, the table must include the line it was found from, and the first line on the page
+/// must have a timestamp.
+pub fn find_structures(dump: &MemoryDump,
+                       table: FoundTable,
+                       pointer_width: Width,
+                       index_width: Width) -> Vec<FoundStructure> {
+  let mut chat_pointers = Vec::new();
+  dump.scan(pointer_width, |address, pointer| {
+    if pointer == table.chat {
+      chat_pointers.push(address);
+    }
+  });
+  let first_timestamp = dump.read(table.chat, 4).ok().map(|b| Width::U32.decode(&b));
+  if !first_timestamp.map(is_timestamp).unwrap_or(false) {
+    return Vec::new();
+  }
+  let size = index_width.size();
+  let mut structures = Vec::new();
+  for chat_pointer in chat_pointers {
+    let (window_start, ref window) = match dump.around(chat_pointer, MAX_STRUCTURE_SIZE) {
+      Some(w) => w,
+      None => continue
+    };
+    // Look for a pointer just past the end of the table that includes the line's index. The crate
+    // builds with compilers older than `is_multiple_of`.
+    #[allow(unknown_lints, clippy::manual_is_multiple_of)]
+    let index_pointers = (0..(window.len() + 1).saturating_sub(pointer_width.size()))
+      .step_by(pointer_width.size())
+      .map(|i| (window_start + i, pointer_width.decode(&window[i..])))
+      .filter(|&(_, end)| end > table.index && (end - table.index) % size == 0);
+    for (index_pointer, table_end) in index_pointers {
+      // Look for a line count that puts the start of the table at or before the line's index
+      for i in (0..window.len().saturating_sub(3)).step_by(4) {
+        let count_address = window_start + i;
+        let count = Width::U32.decode(&window[i..]);
+        let page_lines = count % LINES_PER_PAGE as usize;
+        let overlaps = |address: usize, length: usize| {
+          count_address < address + length && address < count_address + 4
+        };
+        if page_lines < 2
+          || overlaps(chat_pointer, pointer_width.size())
+          || overlaps(index_pointer, pointer_width.size())
+          || table_end < size * page_lines
+          || table_end - size * page_lines >= table.index {
+          continue;
+        }
+        let address = count_address.min(index_pointer).min(chat_pointer);
+        structures.push(FoundStructure {
+          address,
+          line_count_offset: count_address - address,
+          index_pointer_offset: index_pointer - address,
+          chat_pointer_offset: chat_pointer - address
+        });
+      }
+    }
+  }
+  structures
+}
+
+/// Finds pointer chains from the module of `dump` to `target`, in the form used by the
+/// `pointer_chain` of an [`OffsetProfile`](../profile/struct.OffsetProfile.html).
+///
+/// At most `max_depth` pointers are followed, and at most `max_offset` is added to each one.
+pub fn find_pointer_chains(dump: &MemoryDump,
+                           target: usize,
+                           pointer_width: Width,
+                           max_depth: usize,
+                           max_offset: usize) -> Vec<Vec<usize>> {
+  let module_start = dump.module_base;
+  let module_size = signature::module_image_size(dump, module_start)
+    .unwrap_or(DEFAULT_MODULE_SIZE);
+  let in_module = |address: usize| address >= module_start && address < module_start + module_size;
+  // For each address found, the address its pointer leads to and the offset added to get there
+  let mut next: HashMap<usize, (usize, usize)> = HashMap::new();
+  let mut statics = Vec::new();
+  if in_module(target) {
+    statics.push(target);
+  }
+  let mut targets = vec![target];
+  for _ in 0..max_depth {
+    if targets.is_empty() {
+      break;
+    }
+    targets.sort();
+    let lowest = targets[0];
+    let highest = targets[targets.len() - 1];
+    let mut found = Vec::new();
+    dump.scan(pointer_width, |address, pointer| {
+      if pointer == 0 || pointer > highest || pointer + max_offset < lowest {
+        return;
+      }
+      let first = match targets.binary_search(&pointer) {
+        Ok(i) | Err(i) => i
+      };
+      for &t in targets[first..].iter().take_while(|t| **t - pointer <= max_offset) {
+        found.push((t - pointer, address, t));
+      }
+    });
+    // Prefer the smallest offsets if there are too many pointers to follow
+    found.sort();
+    let mut new_targets = Vec::new();
+    for (offset, address, t) in found {
+      if address == target || next.contains_key(&address) {
+        continue;
+      }
+      next.insert(address, (t, offset));
+      if in_module(address) {
+        statics.push(address);
+      } else if new_targets.len() < MAX_CHAIN_NODES {
+        new_targets.push(address);
+      }
+    }
+    targets = new_targets;
+  }
+  statics.into_iter().map(|address| {
+    let mut chain = vec![address - module_start];
+    let mut current = address;
+    while let Some(&(t, offset)) = next.get(&current) {
+      chain.push(offset);
+      current = t;
+    }
+    chain
+  }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use byteorder::{ByteOrder, LittleEndian};
+
+  const MODULE: usize = 0x40_0000;
+  const HEAP: usize = 0x1000_0000;
+  const STRUCTURE: usize = 0x2000_0000;
+  const INDEX: usize = 0x3000_0000;
+  const CHAT: usize = 0x3100_0000;
+
+  fn write_u32(region: &mut [u8], offset: usize, value: usize) {
+    LittleEndian::write_u32(&mut region[offset..], value as u32);
+  }
+
+  fn line(timestamp: usize, sender: &str, message: &str) -> Vec<u8> {
+    let mut line = vec![0; 8];
+    write_u32(&mut line, 0, timestamp);
+    line[4] = 0x0a;
+    line.extend(format!(":{}:{}", sender, message).as_bytes());
+    line
+  }
+
+  /// Creates a dump of a game with the chat log structure at `STRUCTURE`, found through a pointer
+  /// at `MODULE + 0x100` to `HEAP` and a pointer at `HEAP + 0x10` to the structure.
+  fn dump(messages: &[&str]) -> MemoryDump {
+    let mut module = vec![0; 0x1000];
+    module[..2].copy_from_slice(b"MZ");
+    write_u32(&mut module, 0x3c, 0x80);
+    module[0x80..0x84].copy_from_slice(b"PE\0\0");
+    write_u32(&mut module, 0xd0, 0x1000);
+    write_u32(&mut module, 0x100, HEAP);
+    let mut heap = vec![0; 0x100];
+    write_u32(&mut heap, 0x10, STRUCTURE);
+    let mut chat = Vec::new();
+    let mut index = Vec::new();
+    for (i, message) in messages.iter().enumerate() {
+      chat.extend(line(1_500_000_000 + i, "Sender", message));
+      index.extend(&[0; 4]);
+      let end = index.len() - 4;
+      write_u32(&mut index, end, chat.len());
+    }
+    let mut structure = vec![0; 0x100];
+    write_u32(&mut structure, 0, 1000 + messages.len());
+    write_u32(&mut structure, 40, INDEX + index.len());
+    write_u32(&mut structure, 52, CHAT);
+    MemoryDump::from_regions("ffxiv_dx11.exe", MODULE, vec![
+      (MODULE, module),
+      (HEAP, heap),
+      (STRUCTURE, structure),
+      (INDEX, index),
+      (CHAT, chat)
+    ])
+  }
+
+  #[test]
+  fn finds_the_chat_log() {
+    let dump = dump(&["first", "second", "the line just sent"]);
+    assert!(dump.has_module_memory());
+    let profiles = discover(&dump, "the line just sent", &DiscoveryOptions::default());
+    let expected = OffsetProfile {
+      pointer_chain: vec![0x100, 0x10, 0],
+      ..OffsetProfile::default()
+    };
+    assert_eq!(profiles.first(), Some(&expected));
+    assert!(discover(&dump, "not sent", &DiscoveryOptions::default()).is_empty());
+  }
+
+  #[test]
+  fn needs_the_module_memory() {
+    let dump = MemoryDump::from_regions("ffxiv_dx11.exe", MODULE, vec![(CHAT, vec![0; 0x100])]);
+    assert!(!dump.has_module_memory());
+  }
+
+  #[test]
+  fn finds_all_matches() {
+    assert_eq!(find_all(b"abcabab", b"ab"), vec![0, 3, 5]);
+    assert_eq!(find_all(b"aaa", b"aa"), vec![0, 1]);
+    assert!(find_all(b"a", b"ab").is_empty());
+  }
+}
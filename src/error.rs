@@ -0,0 +1,150 @@
+//! Errors reported by readers
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fmt::Result as FmtResult;
+use std::io;
+use std::path::PathBuf;
+
+/// An error encountered while starting or running a reader.
+#[derive(Debug)]
+pub enum ReaderError {
+  /// The reader has already been started and has not been stopped.
+  AlreadyStarted,
+  /// Permission to read the process's memory was denied.
+  PermissionDenied(io::Error),
+  /// The process could not be found.
+  ProcessNotFound,
+  /// The module the pointer chain starts from is not loaded in the process.
+  ModuleNotFound(String),
+  /// A pointer in the pointer chain was null or pointed to memory that can't be read.
+  InvalidPointerChain,
+  /// The game's version has no profile in the reader's
+  /// [`ProfileRegistry`](../registry/struct.ProfileRegistry.html). Holds the version hash that
+  /// was found.
+  UnknownGameVersion(u64),
+  /// Memory at a resolved address could not be read.
+  ReadFailed {
+    /// The address that was read from.
+    address: usize,
+    /// The number of bytes that were read.
+    length: usize,
+    /// The underlying error.
+    error: io::Error
+  },
+  /// A file could not be opened or read.
+  Io(io::Error),
+  /// A line of an ACT log doesn't match its hash.
+  ///
+  /// See [`act::hash`](../act/hash/index.html).
+  InvalidLineHash {
+    /// The log the line is in.
+    file: PathBuf,
+    /// The number of the line in the log, counting from 1.
+    line: u64
+  },
+  /// An adaptive [`PollInterval`](../poll/enum.PollInterval.html) has a `min` longer than its
+  /// `max`.
+  InvalidPollInterval,
+  /// An error from one of the clients of a
+  /// [`MultiClientReader`](../multi/struct.MultiClientReader.html), with the PID of the client.
+  Client(u32, Box<ReaderError>)
+}
+
+impl ReaderError {
+  /// Converts an error from opening a memory source.
+  ///
+  /// Errors of kind `PermissionDenied` and `NotFound` become `PermissionDenied` and
+  /// `ProcessNotFound`. Anything else becomes `Io`.
+  pub fn from_open(error: io::Error) -> Self {
+    match error.kind() {
+      io::ErrorKind::PermissionDenied => ReaderError::PermissionDenied(error),
+      io::ErrorKind::NotFound => ReaderError::ProcessNotFound,
+      _ => ReaderError::Io(error)
+    }
+  }
+
+  /// Converts an error from reading `length` bytes of memory at `address`.
+  ///
+  /// Errors of kind `PermissionDenied` and `NotFound` become `PermissionDenied` and
+  /// `ProcessNotFound`. Anything else becomes `ReadFailed`.
+  pub fn from_read(error: io::Error, address: usize, length: usize) -> Self {
+    match error.kind() {
+      io::ErrorKind::PermissionDenied => ReaderError::PermissionDenied(error),
+      io::ErrorKind::NotFound => ReaderError::ProcessNotFound,
+      _ => ReaderError::ReadFailed {
+        address,
+        length,
+        error
+      }
+    }
+  }
+}
+
+impl Display for ReaderError {
+  fn fmt(&self, f: &mut Formatter) -> FmtResult {
+    match *self {
+      ReaderError::AlreadyStarted => write!(f, "the reader has already been started"),
+      ReaderError::PermissionDenied(ref e) => write!(f, "permission to read memory denied: {}", e),
+      ReaderError::ProcessNotFound => write!(f, "the process could not be found"),
+      ReaderError::ModuleNotFound(ref m) => write!(f, "the module {} is not loaded", m),
+      ReaderError::InvalidPointerChain => write!(f, "the pointer chain is invalid"),
+      ReaderError::UnknownGameVersion(hash) => {
+        write!(f, "there is no profile for the game version with hash {:016x}", hash)
+      },
+      ReaderError::ReadFailed { address, length, ref error } => {
+        write!(f, "could not read {} bytes at {:#x}: {}", length, address, error)
+      },
+      ReaderError::Io(ref e) => write!(f, "{}", e),
+      ReaderError::InvalidLineHash { ref file, line } => {
+        write!(f, "line {} of {} doesn't match its hash", line, file.display())
+      },
+      ReaderError::InvalidPollInterval => {
+        write!(f, "the poll interval's minimum is longer than its maximum")
+      },
+      ReaderError::Client(pid, ref e) => write!(f, "client {}: {}", pid, e)
+    }
+  }
+}
+
+impl Error for ReaderError {
+  fn source(&self) -> Option<&(dyn Error + 'static)> {
+    match *self {
+      ReaderError::PermissionDenied(ref e)
+        | ReaderError::ReadFailed { error: ref e, .. }
+        | ReaderError::Io(ref e) => Some(e),
+      ReaderError::Client(_, ref e) => Some(&**e),
+      _ => None
+    }
+  }
+}
+
+impl From<io::Error> for ReaderError {
+  fn from(e: io::Error) -> Self {
+    ReaderError::Io(e)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn classifies_io_errors() {
+    let error = |kind| io::Error::new(kind, "test");
+    match ReaderError::from_open(error(io::ErrorKind::PermissionDenied)) {
+      ReaderError::PermissionDenied(_) => {},
+      e => panic!("expected permission to be denied, got {:?}", e)
+    }
+    match ReaderError::from_open(error(io::ErrorKind::NotFound)) {
+      ReaderError::ProcessNotFound => {},
+      e => panic!("expected a missing process, got {:?}", e)
+    }
+    match ReaderError::from_read(error(io::ErrorKind::Other), 0x10, 4) {
+      ReaderError::ReadFailed { address: 0x10, length: 4, .. } => {},
+      e => panic!("expected a failed read, got {:?}", e)
+    }
+    let e = ReaderError::from_read(error(io::ErrorKind::Other), 0x10, 4);
+    assert_eq!(e.to_string(), "could not read 4 bytes at 0x10: test");
+  }
+}
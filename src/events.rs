@@ -0,0 +1,58 @@
+//! Events sent by readers
+
+use error::ReaderError;
+
+/// An event sent from a reader's loop.
+///
+/// Most events are items, but readers also report changes in their status so that consumers can
+/// tell when nothing is being read.
+#[derive(Debug)]
+pub enum ReaderEvent<T> {
+  /// An item that was read.
+  Item(T),
+  /// The reader can no longer read from its source and is waiting for it to become valid again.
+  Detached,
+  /// The reader has reattached to its source and will resume reading.
+  Attached,
+  /// This many lines were overwritten before they could be read.
+  Gap(u32),
+  /// This many items were dropped because the channel they were sent through was full.
+  ///
+  /// See [`channel`](../channel/index.html).
+  Dropped(u64),
+  /// An error was encountered while reading.
+  ///
+  /// If the reader can't recover from the error, this is the last event sent.
+  Error(ReaderError)
+}
+
+impl<T> ReaderEvent<T> {
+  /// Gets the item in this event, if it is one.
+  pub fn item(self) -> Option<T> {
+    match self {
+      ReaderEvent::Item(t) => Some(t),
+      _ => None
+    }
+  }
+
+  /// Checks if this event is an item.
+  pub fn is_item(&self) -> bool {
+    matches!(*self, ReaderEvent::Item(_))
+  }
+
+  /// Converts the item in this event using `f`, passing other events through unchanged.
+  ///
+  /// Returns `None` if this event is an item and `f` returns `None` for it.
+  pub fn filter_map<U, F>(self, f: F) -> Option<ReaderEvent<U>>
+    where F: FnOnce(T) -> Option<U>
+  {
+    match self {
+      ReaderEvent::Item(t) => f(t).map(ReaderEvent::Item),
+      ReaderEvent::Detached => Some(ReaderEvent::Detached),
+      ReaderEvent::Attached => Some(ReaderEvent::Attached),
+      ReaderEvent::Gap(n) => Some(ReaderEvent::Gap(n)),
+      ReaderEvent::Dropped(n) => Some(ReaderEvent::Dropped(n)),
+      ReaderEvent::Error(e) => Some(ReaderEvent::Error(e))
+    }
+  }
+}
@@ -0,0 +1,24 @@
+//! 64-bit FNV-1a hashing
+//!
+//! Hashes that are saved to files or compared across runs use FNV-1a, since it stays the same
+//! across versions of Rust and of this crate, unlike the standard library's hashers.
+
+const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const PRIME: u64 = 0x0100_0000_01b3;
+
+/// Hashes `bytes` with 64-bit FNV-1a.
+pub fn hash(bytes: &[u8]) -> u64 {
+  bytes.iter().fold(OFFSET_BASIS, |hash, b| (hash ^ *b as u64).wrapping_mul(PRIME))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn matches_reference_values() {
+    assert_eq!(hash(b""), 0xcbf2_9ce4_8422_2325);
+    assert_eq!(hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+    assert_eq!(hash(b"foobar"), 0x8594_4171_f739_67e8);
+  }
+}
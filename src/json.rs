@@ -0,0 +1,200 @@
+//! Reading entries from JSON-lines files
+//!
+//! These are the files written by `output_json`: one entry serialized as JSON per line.
+
+use serde_json;
+
+use error::ReaderError;
+use events::ReaderEvent;
+use messages::entries::Entry;
+use channel::{self, EventReceiver, OverflowPolicy};
+use poll::PollInterval;
+
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+/// A reader for files with one JSON entry per line.
+///
+/// Lines that don't start with `{` are ignored, so output that was mixed in with the entries, such
+/// as error messages, is skipped. A line that starts with `{` but isn't a valid entry is sent as an
+/// `Error` event naming its line number, and reading continues.
+pub struct JsonEntryReader {
+  path: PathBuf,
+  /// Whether the reader will stop once it reaches the end of the file.
+  pub stop: bool,
+  /// Whether lines that don't start with `{` are sent as `Error` events instead of being ignored.
+  ///
+  /// Empty lines are always ignored.
+  pub strict: bool,
+  /// How long to wait before checking for new lines after reaching the end of the file.
+  pub poll: PollInterval,
+  run: Arc<AtomicBool>
+}
+
+impl JsonEntryReader {
+  /// Create a new reader for the file at `path`.
+  ///
+  /// `stop` denotes whether the reader will stop once it reaches the end of the file, or wait for
+  /// more lines to be written.
+  pub fn new<P: AsRef<Path>>(path: P, stop: bool) -> JsonEntryReader {
+    JsonEntryReader {
+      path: path.as_ref().to_path_buf(),
+      stop,
+      strict: false,
+      poll: PollInterval::default(),
+      run: Arc::new(AtomicBool::new(false))
+    }
+  }
+
+  /// The path to the file being read.
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+
+  /// Starts reading the file.
+  ///
+  /// This will fail if `start` has already been called, if the file can't be opened or if `poll` is
+  /// an invalid interval.
+  pub fn start(&self) -> Result<EventReceiver<Entry>, ReaderError> {
+    if self.run.load(Ordering::Relaxed) {
+      return Err(ReaderError::AlreadyStarted);
+    }
+    let mut reader = BufReader::new(File::open(&self.path)?);
+    let (tx, rx) = channel::channel(None, OverflowPolicy::Block);
+    let stop = self.stop;
+    let strict = self.strict;
+    let mut timer = self.poll.timer()?;
+    self.run.store(true, Ordering::Relaxed);
+    let run = self.run.clone();
+    thread::spawn(move || {
+      let mut line = String::new();
+      let mut line_number = 0;
+      while run.load(Ordering::Relaxed) {
+        let size = match reader.read_line(&mut line) {
+          Ok(s) => s,
+          Err(e) => {
+            tx.send(ReaderEvent::Error(ReaderError::Io(e))).ok();
+            break;
+          }
+        };
+        if size == 0 {
+          if stop {
+            break;
+          }
+          thread::sleep(timer.idle());
+          continue;
+        }
+        timer.found();
+        // Wait for the rest of a line that is still being written
+        if !line.ends_with('\n') && !stop {
+          continue;
+        }
+        line_number += 1;
+        let event = if line.starts_with('{') {
+          match serde_json::from_str(&line) {
+            Ok(entry) => Some(ReaderEvent::Item(entry)),
+            Err(e) => Some(invalid_line(line_number, e))
+          }
+        } else if strict && !line.trim().is_empty() {
+          Some(invalid_line(line_number, "not a JSON object"))
+        } else {
+          None
+        };
+        line.clear();
+        if let Some(event) = event {
+          try_or!(tx.send(event), break);
+        }
+      }
+    });
+    Ok(rx)
+  }
+
+  /// Reads every entry in the file, waiting for more lines unless `stop` is set.
+  ///
+  /// If any line couldn't be read, every error is returned instead of the entries.
+  pub fn read_all(&self) -> Result<Vec<Entry>, Vec<ReaderError>> {
+    let rx = self.start().map_err(|e| vec![e])?;
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+    for event in rx {
+      match event {
+        ReaderEvent::Item(entry) => entries.push(entry),
+        ReaderEvent::Error(e) => errors.push(e),
+        _ => {}
+      }
+    }
+    self.stop();
+    if errors.is_empty() {
+      Ok(entries)
+    } else {
+      Err(errors)
+    }
+  }
+
+  /// Stops reading the file.
+  ///
+  /// This is called automatically when the reader is dropped.
+  pub fn stop(&self) {
+    self.run.store(false, Ordering::Relaxed);
+  }
+}
+
+/// Creates the error sent for a line that isn't a valid entry.
+fn invalid_line<E: Display>(number: u64, error: E) -> ReaderEvent<Entry> {
+  let message = format!("line {}: {}", number, error);
+  ReaderEvent::Error(ReaderError::Io(io::Error::new(io::ErrorKind::InvalidData, message)))
+}
+
+impl Drop for JsonEntryReader {
+  fn drop(&mut self) {
+    self.stop();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::env;
+  use std::fs;
+  use std::io::Write;
+
+  fn write_file(name: &str, content: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("ffxiv_reader_{}_{}.json", name, ::std::process::id()));
+    File::create(&path).unwrap().write_all(content.as_bytes()).unwrap();
+    path
+  }
+
+  #[test]
+  fn reports_invalid_lines() {
+    let path = write_file("invalid", "Error reading memory\n\n{\"timestamp\": 1}\n");
+    let mut reader = JsonEntryReader::new(&path, true);
+    match reader.read_all() {
+      Err(ref errors) if errors.len() == 1 => assert!(errors[0].to_string().contains("line 3")),
+      r => panic!("expected one error, got {:?}", r)
+    }
+    reader.strict = true;
+    let errors: Vec<String> = reader.read_all().unwrap_err()
+      .iter()
+      .map(|e| e.to_string())
+      .collect();
+    assert_eq!(errors.len(), 2);
+    assert!(errors[0].contains("line 1: not a JSON object"));
+    assert!(errors[1].contains("line 3"));
+    fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn reads_empty_files() {
+    let path = write_file("empty", "\n");
+    let mut reader = JsonEntryReader::new(&path, true);
+    reader.strict = true;
+    assert!(reader.read_all().unwrap().is_empty());
+    fs::remove_file(&path).unwrap();
+  }
+}
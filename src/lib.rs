@@ -6,13 +6,16 @@ extern crate serde_json;
 #[macro_use]
 extern crate lazy_static;
 extern crate chrono;
+extern crate md5;
+extern crate sha2;
+#[cfg(feature = "stream")]
+extern crate futures;
+#[cfg(feature = "stream")]
+extern crate tokio;
 
-use byteorder::{LittleEndian, ByteOrder};
-use std::sync::mpsc::Receiver;
-use std::sync::Arc;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::io::Read;
-use memreader::prelude::*;
 
 macro_rules! opt {
   ($e:expr) => (opt_or_else!($e, None))
@@ -41,49 +44,50 @@ macro_rules! try_or {
   }}
 }
 
-macro_rules! read {
-  ($e:expr, $n:expr) => {{
-    let mut buf = vec![0; $n];
-    $e.read_exact(&mut buf).map(|_| buf)
-  }}
-}
-
 pub mod messages;
 pub mod act;
+pub mod memory;
+pub mod profile;
+pub mod signature;
+pub mod process;
+pub mod events;
+pub mod snapshot;
+pub mod error;
+pub mod json;
+pub mod source;
+pub mod checkpoint;
+pub mod channel;
+pub mod multi;
+pub mod poll;
+pub mod registry;
+pub mod discovery;
+pub mod player;
+#[cfg(feature = "stream")]
+pub mod stream;
 
-pub use act::ActReader;
-
-use messages::entries::{Entry, RawEntry};
+mod chat_log;
+mod fnv;
+#[cfg(test)]
+mod testing;
 
-fn get_base_address(reader: Option<&MemReader>) -> Option<usize> {
-  let reader = opt!(reader);
-  reader.base_address("ffxiv_dx11.exe").ok()
-}
-
-fn get_lines_address(reader: Option<&MemReader>) -> Option<usize> {
-  let reader = opt!(reader);
-  let base_address = opt!(get_base_address(Some(reader)));
-  let pointer_1 = base_address + 0x017a4138;
-  let value_1 = try_or!(read!(reader.address_slice_len(pointer_1, 4), 4), return None);
-  let pointer_2 = LittleEndian::read_u32(&value_1) as usize + 0x60;
-  let value_2 = try_or!(read!(reader.address_slice_len(pointer_2, 4), 4), return None);
-  Some(LittleEndian::read_u32(&value_2) as usize + 0x2b8)
-}
+pub use act::{ActReader, ActLine};
+pub use json::JsonEntryReader;
+pub use source::EntrySource;
+pub use memory::{MemorySource, ProcessMemory, InMemorySource};
+pub use profile::{OffsetProfile, Width};
+pub use events::ReaderEvent;
+pub use channel::{EventReceiver, OverflowPolicy};
+pub use multi::MultiClientReader;
+pub use poll::PollInterval;
+pub use registry::ProfileRegistry;
+pub use player::LocalPlayer;
+pub use error::ReaderError;
+#[cfg(feature = "stream")]
+pub use stream::{MemoryEntryStream, ActEntryStream};
 
-fn get_chat_pointer(lines_address: usize) -> usize {
-  lines_address + 52
-}
-
-fn get_index_pointer(lines_address: usize) -> usize {
-  lines_address + 40
-}
-
-// TODO: Handle the game closing, logging out, disconnects, etc. better. Wait for pointers to become
-//       valid again, then start reading again.
-
-fn to_hex_string(bytes: &[u8]) -> String {
-  bytes.iter().map(|x| format!("{:02X}", x)).collect::<Vec<_>>().join(" ")
-}
+use messages::entries::{Entry, EntryError, RawEntry};
+use chat_log::ChatLog;
+use checkpoint::{Checkpoint, MemoryCheckpoint};
 
 fn read_var_le(bytes: &[u8]) -> Option<u64> {
   if bytes.len() == 1 {
@@ -117,113 +121,248 @@ fn read_var_le(bytes: &[u8]) -> Option<u64> {
 ///   println!("{:?}", entry);
 /// }
 /// ```
-pub struct MemoryEntryReader {
-  pub pid: u32,
+///
+/// The reader is generic over its [`MemorySource`](memory/trait.MemorySource.html). `new` reads
+/// from a running game, and `with_source` can be used to read from anything else, such as an
+/// [`InMemorySource`](memory/struct.InMemorySource.html).
+///
+/// The chat log is found using the reader's [`OffsetProfile`](profile/struct.OffsetProfile.html),
+/// which can be replaced before the reader is started. A reader for a running game first checks
+/// the game's version against the built-in [`registry`](registry/index.html), if there is one, and
+/// fails with `UnknownGameVersion` for a build the default profile hasn't been checked against. Set
+/// `registry` to `None` to read any build with `profile`.
+///
+/// If `reconnect` is set, the reader survives the game closing, logging out or reloading the chat
+/// log. A `Detached` [`ReaderEvent`](events/enum.ReaderEvent.html) is sent when the memory can no
+/// longer be read, and the reader waits for the pointers to become valid again, finding the game
+/// again by name if it was restarted. Messages that were already read are not read again.
+///
+/// The game only keeps the last page of up to 1000 lines in memory. If the reader falls so far
+/// behind that lines are overwritten before they are read, a `Gap` event is sent with the number of
+/// lines lost. The iterator skips these events.
+///
+/// Entries that can't be parsed are skipped by the iterator. To see them, use `try_iter`, which
+/// yields an [`EntryError`](messages/entries/struct.EntryError.html) holding the raw bytes of each
+/// one.
+pub struct MemoryEntryReader<S = ProcessMemory> {
   pub stop: bool,
+  /// Whether to wait for the memory to become readable again instead of stopping.
+  pub reconnect: bool,
+  /// The offsets used to find the chat log in memory.
+  pub profile: OffsetProfile,
+  /// Profiles for known versions of the game.
+  ///
+  /// If set, the profile for the running version of the game is used instead of `profile`, and
+  /// the reader fails with `UnknownGameVersion` if there isn't one. This is the built-in registry
+  /// for readers created with `new` or `from_process_name`, which is `None` until a build has been
+  /// added to it, and `None` for readers created with `with_source`. See
+  /// [`registry`](registry/index.html).
+  pub registry: Option<ProfileRegistry>,
+  /// A file to resume from and save the reader's position to.
+  ///
+  /// See [`checkpoint`](checkpoint/index.html).
+  pub checkpoint: Option<PathBuf>,
+  /// The number of entries that can be waiting to be received before `overflow` applies.
+  ///
+  /// If this is `None`, any number of entries can be waiting.
+  pub capacity: Option<usize>,
+  /// What to do with new entries when `capacity` entries are waiting to be received.
+  pub overflow: OverflowPolicy,
+  /// How long to wait before checking for new messages after finding none.
+  pub poll: PollInterval,
+  source: Arc<S>,
+  local_player: Arc<Mutex<Option<LocalPlayer>>>,
   run: Arc<AtomicBool>
 }
 
-impl MemoryEntryReader {
+impl MemoryEntryReader<ProcessMemory> {
   /// Create a new reader.
   ///
   /// Reads from the process using PID `pid`. `stop` denotes whether the reader will stop once it
   /// runs out of messages.
   pub fn new(pid: u32, stop: bool) -> Self {
+    MemoryEntryReader::for_process(ProcessMemory::new(pid), stop)
+  }
+
+  /// Create a new reader for the newest running process matching `name`.
+  ///
+  /// Returns `None` if no process matches. Use
+  /// [`process::GAME_PROCESS_NAME`](process/constant.GAME_PROCESS_NAME.html) to find the game.
+  pub fn from_process_name(name: &str, stop: bool) -> Option<Self> {
+    ProcessMemory::from_name(name).map(|source| MemoryEntryReader::for_process(source, stop))
+  }
+
+  /// Creates a reader for a running game, which only reads the builds in the built-in registry if
+  /// there is one.
+  fn for_process(source: ProcessMemory, stop: bool) -> Self {
+    let mut reader = MemoryEntryReader::with_source(source, stop);
+    reader.registry = ProfileRegistry::builtin();
+    reader
+  }
+}
+
+impl<S> MemoryEntryReader<S>
+  where S: MemorySource + Send + Sync + 'static
+{
+  /// Create a new reader that reads from `source`.
+  ///
+  /// `stop` denotes whether the reader will stop once it runs out of messages.
+  pub fn with_source(source: S, stop: bool) -> Self {
     MemoryEntryReader {
-      pid,
       stop,
+      reconnect: false,
+      profile: OffsetProfile::default(),
+      registry: None,
+      checkpoint: None,
+      capacity: None,
+      overflow: OverflowPolicy::Block,
+      poll: PollInterval::default(),
+      source: Arc::new(source),
+      local_player: Arc::new(Mutex::new(None)),
       run: Arc::new(AtomicBool::new(false))
     }
   }
 
-  /// Starts the memory reading loop.
+  /// Gets the source this reader reads from.
+  pub fn source(&self) -> &S {
+    &self.source
+  }
+
+  /// Gets the logged-in character, as last read by the reader.
   ///
-  /// This is automatically called when the reader is used as an iterator. This can be used when not
-  /// using a loop to get the raw bytes for each entry from the `Receiver`.
+  /// The character is read each time the reader attaches to the chat log. This is `None` if the
+  /// reader hasn't attached yet, or if the profile doesn't say where to find the character's name.
   ///
-  /// This will return `None` if `start` has already been called or if the memory can't be read.
-  pub fn start(&self) -> Option<Receiver<Vec<u8>>> {
+  /// Entries sent by this character are marked as
+  /// [`outgoing`](messages/entries/struct.Entry.html#structfield.outgoing).
+  pub fn local_player(&self) -> Option<LocalPlayer> {
+    self.local_player.lock().unwrap().clone()
+  }
+
+  /// Starts the memory reading loop, sending the raw bytes of each entry.
+  ///
+  /// This can be used when not using a loop to get the raw bytes for each entry from the
+  /// `EventReceiver`, along with any status events. Use
+  /// [`EntrySource::start`](source/trait.EntrySource.html#tymethod.start) to get parsed entries
+  /// instead.
+  ///
+  /// This will fail if the reader has already been started or if the memory can't be read. If
+  /// `reconnect` is `true`, the loop is started even if the memory can't be read, and the error is
+  /// sent followed by a `Detached` event.
+  ///
+  /// Errors encountered by the loop are sent as `Error` events. Without `reconnect`, the loop stops
+  /// after sending one.
+  pub fn start_raw(&self) -> Result<EventReceiver<Vec<u8>>, ReaderError> {
+    self.spawn(|bytes, _| Some(bytes))
+  }
+
+  /// Starts the memory reading loop, converting the bytes of each entry with `map`, which is also
+  /// given the logged-in character.
+  ///
+  /// Entries that `map` returns `None` for are not sent.
+  fn spawn<T, F>(&self, map: F) -> Result<EventReceiver<T>, ReaderError>
+    where T: Send + 'static,
+          F: Fn(Vec<u8>, Option<&LocalPlayer>) -> Option<T> + Send + 'static
+  {
     if self.run.load(Ordering::Relaxed) {
-      return None;
+      return Err(ReaderError::AlreadyStarted);
     }
-    // Create a reader around the PID of the game.
-    let reader = match MemReader::new(self.pid) {
-      Ok(r) => r,
-      Err(e) => {
-        println!("Encountered error {} when trying to access memory.", e);
-        return None;
-      }
+    let mut timer = self.poll.timer()?;
+    let checkpoint_path = self.checkpoint.clone();
+    let mut checkpoint = match checkpoint_path {
+      Some(ref path) => MemoryCheckpoint::load(path)?,
+      None => MemoryCheckpoint::default()
     };
-    let lines_address = opt!(get_lines_address(Some(&reader)));
-    let chat_pointer = get_chat_pointer(lines_address);
-    let index_pointer = get_index_pointer(lines_address);
-    let raw_chat_pointer = opt!(read!(reader.address_slice_len(chat_pointer, 4), 4).ok());
-    let chat_address = LittleEndian::read_u32(&raw_chat_pointer) as usize;
+    let mut resume = checkpoint.resume();
+    let registry = self.registry.clone();
+    let mut chat_log = ChatLog::new(self.source.clone(), self.profile.clone(), registry);
+    let (mut tx, rx) = channel::channel(self.capacity, self.overflow);
+    tx.stop_with(self.run.clone());
+    if let Err(e) = chat_log.attach() {
+      if !self.reconnect {
+        return Err(e);
+      }
+      tx.send(ReaderEvent::Error(e)).ok();
+      tx.send(ReaderEvent::Detached).ok();
+    }
     let stop = self.stop;
-    let (tx, rx) = std::sync::mpsc::channel();
+    let reconnect = self.reconnect;
+    let local_player = self.local_player.clone();
+    // The character can change whenever the chat log is attached, so it is read again each time
+    let read_player = move |chat_log: &ChatLog<S>| {
+      let player = LocalPlayer::read(chat_log.source(), chat_log.profile());
+      *local_player.lock().unwrap() = player.clone();
+      player
+    };
+    let mut player = if chat_log.is_attached() { read_player(&chat_log) } else { None };
     self.run.store(true, Ordering::Relaxed);
     let run = self.run.clone();
     std::thread::spawn(move || {
-      // Index of last read index
-      let mut index_index = 0;
       'main_loop: while run.load(Ordering::Relaxed) {
-        // Get raw bytes for current index pointer
-        let raw_pointer = try_or!(read!(reader.address_slice_len(index_pointer, 4), 4), break);
-        // Read the raw bytes into an address
-        let pointer = LittleEndian::read_u32(&raw_pointer);
-        // Read the total number of lines (modulo 1000 because the game wraps around at 1000)
-        let num_lines = {
-          let raw = try_or!(read!(reader.address_slice_len(lines_address, 4), 4), break);
-          LittleEndian::read_u32(&raw) % 1000
-        };
-        // Read u32s backwards until we hit 0
-        let mut mem_indices = Vec::with_capacity(index_index + 1);
-        loop {
-          // If the amount of lines we've read is equal to the number of lines, break
-          if mem_indices.len() == num_lines as usize {
-            break;
+        // Wait for the pointers to become valid again if reading failed
+        if !chat_log.is_attached() {
+          if chat_log.source().reattach() && chat_log.attach().is_ok() {
+            player = read_player(&chat_log);
+            try_or!(tx.send(ReaderEvent::Attached), break);
+          } else {
+            // Nothing else is sent while waiting, so check here that someone is still listening.
+            if tx.is_disconnected() {
+              break;
+            }
+            std::thread::sleep(std::time::Duration::from_secs(1));
+            continue;
           }
-          // Read backwards, incrementing by four for each index read
-          let raw_index = try_or!(read!(reader.address_slice_len(pointer as usize - (4 * (mem_indices.len() + 1)), 4), 4), break);
-          // Read the raw bytes into a u32
-          let index = LittleEndian::read_u32(&raw_index);
-          // Otherwise, insert the index at the start
-          mem_indices.insert(0, index);
         }
-        // If the number of indices we just read is equal to the last index of the indices we read,
-        // there are no new messages, so sleep and restart the loop.
-        if mem_indices.len() == index_index {
-          if stop {
+        let events = match chat_log.poll() {
+          Ok(e) => e,
+          Err(e) => {
+            try_or!(tx.send(ReaderEvent::Error(e)), break);
+            if !reconnect {
+              break;
+            }
+            try_or!(tx.send(ReaderEvent::Detached), break);
+            continue;
+          }
+        };
+        // If there are no new messages, sleep and restart the loop.
+        if events.is_empty() {
+          if chat_log.source().has_pending_changes() {
+            continue;
+          } else if stop {
             break;
           } else {
-            std::thread::sleep(std::time::Duration::from_millis(100));
+            std::thread::sleep(timer.idle());
             continue;
           }
-        } else if mem_indices.len() < index_index {
-          // If the amount of indices we've read is less than the amount we were at last time,
-          // we've wrapped around in the memory, so reset the index to 0.
-          index_index = 0;
         }
-        // Get all the new indices
-        let new_indices = &mem_indices[index_index..];
-        // Get the last index, or 0 to start
-        let mut last_index = if index_index == 0 {
-          0
-        } else {
-          // The last index will be in the new indices we just read, being the last one we have read
-          mem_indices[index_index - 1]
-        };
-        index_index = mem_indices.len();
-        // Read each new message and send it
-        for index in new_indices {
-          let read_size = *index as usize - last_index as usize;
-          let message = try_or!(read!(reader.address_slice_len(chat_address + last_index as usize, read_size), read_size), break);
-          last_index = *index;
-          try_or!(tx.send(message), break 'main_loop);
+        timer.found();
+        // The character may not have been readable yet when attaching
+        if player.is_none() {
+          player = read_player(&chat_log);
+        }
+        // Send each new message, skipping any delivered before the checkpoint
+        let mut delivered = false;
+        for event in events {
+          let event = event.filter_map(|bytes| {
+            if !resume.is_new(&bytes) {
+              return None;
+            }
+            checkpoint.record(&bytes);
+            delivered = true;
+            map(bytes, player.as_ref())
+          });
+          if let Some(event) = event {
+            try_or!(tx.send(event), break 'main_loop);
+          }
+        }
+        if let (true, Some(path)) = (delivered, checkpoint_path.as_ref()) {
+          if let Err(e) = Checkpoint::Memory(checkpoint.clone()).save(path) {
+            try_or!(tx.send(ReaderEvent::Error(ReaderError::Io(e))), break);
+          }
         }
       }
     });
-    Some(rx)
+    Ok(rx)
   }
 
   /// Stops the memory loop.
@@ -235,43 +374,184 @@ impl MemoryEntryReader {
 
   /// Creates an iterator from this reader.
   ///
-  /// This automatically calls `start`.
+  /// This automatically starts the reader.
   ///
-  /// If `start` has been called but `stop` has not been called, the iterator returned will always
-  /// return `None`.
+  /// If the reader has been started but `stop` has not been called, the iterator returned will
+  /// always return `None`.
   pub fn iter(&self) -> MemoryEntryReaderIterator {
-    MemoryEntryReaderIterator { rx: self.start() }
+    match self.spawn(|bytes, player| Some(parse_entry(bytes, player))) {
+      Ok(rx) => MemoryEntryReaderIterator { rx: Some(rx), error: None },
+      Err(e) => MemoryEntryReaderIterator { rx: None, error: Some(e) }
+    }
+  }
+
+  /// Creates an iterator from this reader that also yields entries that couldn't be parsed.
+  ///
+  /// This behaves like `iter`, except that each entry is returned as a `Result`.
+  pub fn try_iter(&self) -> MemoryEntryReaderTryIterator {
+    MemoryEntryReaderTryIterator { inner: self.iter() }
   }
 }
 
-impl Drop for MemoryEntryReader {
+impl<S> Drop for MemoryEntryReader<S> {
   fn drop(&mut self) {
-    self.stop();
+    self.run.store(false, Ordering::Relaxed);
   }
 }
 
+/// Parses the bytes of an entry, marking it as outgoing if it was sent by `player`.
+fn parse_entry(bytes: Vec<u8>, player: Option<&LocalPlayer>) -> Result<Entry, EntryError> {
+  let mut entry = RawEntry::new(bytes).into_entry()?;
+  entry.outgoing = player.map(|p| p.is_sender(&entry)).unwrap_or(false);
+  Ok(entry)
+}
+
 /// The iterator for [`MemoryEntryReader`](struct.MemoryEntryReader.html).
 ///
 /// See [`MemoryEntryReader`](struct.MemoryEntryReader.html) for more information.
+///
+/// Errors are not returned by the iterator. The last error encountered, including any error from
+/// starting the reader, can be checked with `error()`.
 pub struct MemoryEntryReaderIterator {
-  rx: Option<Receiver<Vec<u8>>>
+  rx: Option<EventReceiver<Result<Entry, EntryError>>>,
+  error: Option<ReaderError>
 }
 
-impl Iterator for MemoryEntryReaderIterator {
-  type Item = Entry;
+impl MemoryEntryReaderIterator {
+  /// Gets the last error encountered by the reader, if any.
+  pub fn error(&self) -> Option<&ReaderError> {
+    self.error.as_ref()
+  }
 
-  fn next(&mut self) -> Option<Entry> {
+  fn next_result(&mut self) -> Option<Result<Entry, EntryError>> {
     let rx = match self.rx {
       Some(ref r) => r,
       None => return None
     };
-    let bytes = match rx.recv() {
-      Ok(b) => b,
-      Err(_) => return None
+    loop {
+      match rx.recv() {
+        Ok(ReaderEvent::Item(entry)) => return Some(entry),
+        Ok(ReaderEvent::Error(e)) => self.error = Some(e),
+        Ok(_) => continue,
+        Err(_) => return None
+      }
+    }
+  }
+}
+
+impl Iterator for MemoryEntryReaderIterator {
+  type Item = Entry;
+
+  fn next(&mut self) -> Option<Entry> {
+    loop {
+      match self.next_result() {
+        Some(Ok(entry)) => return Some(entry),
+        Some(Err(_)) => continue,
+        None => return None
+      }
+    }
+  }
+}
+
+/// The iterator for [`MemoryEntryReader::try_iter`](struct.MemoryEntryReader.html#method.try_iter).
+pub struct MemoryEntryReaderTryIterator {
+  inner: MemoryEntryReaderIterator
+}
+
+impl MemoryEntryReaderTryIterator {
+  /// Gets the last error encountered by the reader, if any.
+  pub fn error(&self) -> Option<&ReaderError> {
+    self.inner.error()
+  }
+}
+
+impl Iterator for MemoryEntryReaderTryIterator {
+  type Item = Result<Entry, EntryError>;
+
+  fn next(&mut self) -> Option<Result<Entry, EntryError>> {
+    self.inner.next_result()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use messages::HasDisplayText;
+  use messages::entries::EntryErrorReason;
+  use testing;
+
+  /// Creates a game whose chat log has a bad entry between two good ones.
+  fn game_with_bad_entries() -> InMemorySource {
+    let source = testing::game();
+    testing::write_lines(&source, &[
+      testing::line(1_500_000_000, "Sender", "first"),
+      b"short".to_vec(),
+      b"\x01\x00\x00\x00\x0a\x00\x00\x00:no separator".to_vec(),
+      testing::line(1_500_000_001, "Sender", "second")
+    ]);
+    source
+  }
+
+  #[test]
+  fn try_iter_reports_bad_entries() {
+    let reader = MemoryEntryReader::with_source(game_with_bad_entries(), true);
+    let results: Vec<_> = reader.try_iter().collect();
+    assert_eq!(results.len(), 4);
+    assert_eq!(results[0].as_ref().unwrap().message.display_text(), "first");
+    let error = results[1].as_ref().unwrap_err();
+    assert_eq!(error.reason, EntryErrorReason::TooShort(5));
+    assert_eq!(error.bytes, b"short");
+    let error = results[2].as_ref().unwrap_err();
+    assert_eq!(error.reason, EntryErrorReason::MissingSenderSeparator);
+    assert_eq!(results[3].as_ref().unwrap().message.display_text(), "second");
+  }
+
+  #[test]
+  fn reads_the_default_build_by_default() {
+    // No builds have been added to the built-in registry, so readers for a running game read
+    // whatever is running with the default profile. Once one is, this needs the PE headers of that
+    // build in the fake game.
+    let registry = MemoryEntryReader::new(::std::process::id(), true).registry.clone();
+    assert_eq!(registry, ProfileRegistry::builtin());
+    assert_eq!(registry, None);
+    let source = testing::game();
+    testing::write_lines(&source, &[testing::line(1_500_000_000, "Sender", "hello")]);
+    let mut reader = MemoryEntryReader::with_source(source, true);
+    reader.registry = registry;
+    let mut iter = reader.iter();
+    assert_eq!(iter.by_ref().count(), 1);
+    assert!(iter.error().is_none());
+  }
+
+  #[test]
+  fn resumes_from_checkpoints() {
+    let name = format!("ffxiv_reader_memory_resume_{}.checkpoint", ::std::process::id());
+    let checkpoint = ::std::env::temp_dir().join(name);
+    // More lines in one second than a checkpoint used to remember
+    let mut lines: Vec<_> = (0..40)
+      .map(|i| testing::line(1_500_000_000, "Sender", &format!("hit {}", i)))
+      .collect();
+    let read = |lines: &[Vec<u8>]| {
+      let source = testing::game();
+      testing::write_lines(&source, lines);
+      let mut reader = MemoryEntryReader::with_source(source, true);
+      reader.checkpoint = Some(checkpoint.clone());
+      let messages: Vec<_> = reader.iter().map(|e| e.message.display_text()).collect();
+      messages
     };
-    let raw = RawEntry::new(bytes);
-    let parts = opt!(raw.as_parts());
-    let entry = parts.as_entry();
-    Some(entry)
+    assert_eq!(read(&lines).len(), 40);
+    lines.push(testing::line(1_500_000_000, "Sender", "hit 40"));
+    assert_eq!(read(&lines), vec!["hit 40"]);
+    assert!(read(&lines).is_empty());
+    ::std::fs::remove_file(&checkpoint).unwrap();
+  }
+
+  #[test]
+  fn iter_skips_bad_entries() {
+    let reader = MemoryEntryReader::with_source(game_with_bad_entries(), true);
+    let mut iter = reader.iter();
+    let messages: Vec<_> = iter.by_ref().map(|e| e.message.display_text()).collect();
+    assert_eq!(messages, vec!["first", "second"]);
+    assert!(iter.error().is_none());
   }
 }
@@ -0,0 +1,374 @@
+//! Sources of memory for [`MemoryEntryReader`](../struct.MemoryEntryReader.html)
+
+use memreader::prelude::*;
+
+use process;
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Something that memory can be read from.
+///
+/// [`MemoryEntryReader`](../struct.MemoryEntryReader.html) is generic over this trait, so it can
+/// read from a running game, an in-memory fake, or anything else that can provide bytes at
+/// addresses.
+pub trait MemorySource {
+  /// Reads exactly `buf.len()` bytes starting at `address` into `buf`.
+  fn read_into(&self, address: usize, buf: &mut [u8]) -> io::Result<()>;
+
+  /// Gets the base address of the module named `module`, if it is loaded.
+  fn base_address(&self, module: &str) -> Option<usize>;
+
+  /// Reads `len` bytes starting at `address`.
+  fn read(&self, address: usize, len: usize) -> io::Result<Vec<u8>> {
+    let mut buf = vec![0; len];
+    self.read_into(address, &mut buf).map(|_| buf)
+  }
+
+  /// Checks that the source can be read from, opening it if needed.
+  ///
+  /// Errors of kind `NotFound` mean the source doesn't exist, and errors of kind `PermissionDenied`
+  /// mean it can't be read. The default implementation does nothing.
+  fn open(&self) -> io::Result<()> {
+    Ok(())
+  }
+
+  /// Called by readers before each poll of the memory.
+  ///
+  /// Sources that change over time on their own, such as replays, can move forward here. The
+  /// default implementation does nothing.
+  fn next_poll(&self) {}
+
+  /// Checks if the source will change on its own before the next poll.
+  ///
+  /// Readers that stop once they run out of messages keep polling while this is `true`. The
+  /// default implementation returns `false`.
+  fn has_pending_changes(&self) -> bool {
+    false
+  }
+
+  /// Prepares the source to be read from again after reads from it have failed.
+  ///
+  /// Returns `false` if the source can't be read from yet. The default implementation does
+  /// nothing.
+  fn reattach(&self) -> bool {
+    true
+  }
+}
+
+impl MemorySource for MemReader {
+  fn read_into(&self, address: usize, buf: &mut [u8]) -> io::Result<()> {
+    self.address_slice_len(address, buf.len()).read_exact(buf)
+  }
+
+  fn base_address(&self, module: &str) -> Option<usize> {
+    MemReader::base_address(self, module).ok()
+  }
+}
+
+/// The memory of a running process.
+///
+/// The process is not opened until memory is first read from it.
+///
+/// When reattaching, the process is opened again. If the process has exited, a new process with the
+/// same name is searched for, so a restarted game is picked up with its new PID. If the name of the
+/// process couldn't be read, reattaching fails once the process has exited.
+pub struct ProcessMemory {
+  name: Option<String>,
+  state: Mutex<ProcessState>
+}
+
+struct ProcessState {
+  pid: u32,
+  reader: Option<MemReader>
+}
+
+impl ProcessMemory {
+  pub fn new(pid: u32) -> Self {
+    ProcessMemory {
+      name: process::process_name(pid),
+      state: Mutex::new(ProcessState {
+        pid,
+        reader: None
+      })
+    }
+  }
+
+  /// Creates a source for the newest running process matching `name`.
+  ///
+  /// Returns `None` if no process matches. See [`process`](../process/index.html) for how processes
+  /// are found.
+  pub fn from_name(name: &str) -> Option<Self> {
+    let pid = opt!(process::find_pid(name));
+    Some(ProcessMemory {
+      name: Some(name.to_owned()),
+      state: Mutex::new(ProcessState {
+        pid,
+        reader: None
+      })
+    })
+  }
+
+  /// The PID of the process currently being read.
+  pub fn pid(&self) -> u32 {
+    self.state.lock().unwrap().pid
+  }
+
+  fn with_reader<F, T>(&self, f: F) -> io::Result<T>
+    where F: FnOnce(&MemReader) -> io::Result<T>
+  {
+    let mut state = self.state.lock().unwrap();
+    if state.reader.is_none() {
+      match MemReader::new(state.pid) {
+        Ok(r) => state.reader = Some(r),
+        Err(e) => {
+          // If the process is running, it couldn't be opened because we aren't allowed to. Without
+          // /proc to check, assume the same.
+          let kind = if Path::new("/proc").exists() && process::process(state.pid).is_none() {
+            io::ErrorKind::NotFound
+          } else {
+            io::ErrorKind::PermissionDenied
+          };
+          return Err(io::Error::new(kind, e.to_string()));
+        }
+      }
+    }
+    match state.reader {
+      Some(ref r) => f(r),
+      None => unreachable!()
+    }
+  }
+}
+
+impl MemorySource for ProcessMemory {
+  fn read_into(&self, address: usize, buf: &mut [u8]) -> io::Result<()> {
+    self.with_reader(|r| r.read_into(address, buf))
+  }
+
+  fn base_address(&self, module: &str) -> Option<usize> {
+    self.with_reader(|r| Ok(MemorySource::base_address(r, module))).ok().and_then(|x| x)
+  }
+
+  fn open(&self) -> io::Result<()> {
+    self.with_reader(|_| Ok(()))
+  }
+
+  fn reattach(&self) -> bool {
+    let mut state = self.state.lock().unwrap();
+    state.reader = None;
+    // A process given by PID can only be read again if it's still running.
+    let name = match self.name {
+      Some(ref n) => n,
+      None => return process::process(state.pid).is_some()
+    };
+    if process::process(state.pid).map(|p| p.matches(name)).unwrap_or(false) {
+      return true;
+    }
+    match process::find_pid(name) {
+      Some(pid) => {
+        state.pid = pid;
+        true
+      },
+      None => false
+    }
+  }
+}
+
+/// A fake memory source backed by byte buffers.
+///
+/// Regions of memory and module base addresses can be written at any time, even while a reader is
+/// reading from the source. Reading from an address that has not been written to fails.
+#[derive(Default)]
+pub struct InMemorySource {
+  regions: Mutex<BTreeMap<usize, Vec<u8>>>,
+  modules: Mutex<HashMap<String, usize>>
+}
+
+impl InMemorySource {
+  pub fn new() -> Self {
+    Default::default()
+  }
+
+  /// Sets the base address of the module named `name`.
+  pub fn set_module<S: Into<String>>(&self, name: S, base_address: usize) {
+    self.modules.lock().unwrap().insert(name.into(), base_address);
+  }
+
+  /// Writes `bytes` at `address`, merging with any regions it touches.
+  pub fn write(&self, address: usize, bytes: &[u8]) {
+    let mut regions = self.regions.lock().unwrap();
+    let end = address + bytes.len();
+    let touching: Vec<usize> = regions.iter()
+      .filter(|&(start, data)| *start <= end && start + data.len() >= address)
+      .map(|(start, _)| *start)
+      .collect();
+    let mut start = address;
+    let mut merged_end = end;
+    for key in &touching {
+      start = start.min(*key);
+      merged_end = merged_end.max(key + regions[key].len());
+    }
+    let mut merged = vec![0; merged_end - start];
+    for key in &touching {
+      let data = regions.remove(key).unwrap();
+      merged[key - start..key - start + data.len()].copy_from_slice(&data);
+    }
+    merged[address - start..end - start].copy_from_slice(bytes);
+    regions.insert(start, merged);
+  }
+
+  /// Removes all written regions and module base addresses.
+  pub fn clear(&self) {
+    self.regions.lock().unwrap().clear();
+    self.modules.lock().unwrap().clear();
+  }
+}
+
+impl MemorySource for InMemorySource {
+  fn read_into(&self, address: usize, buf: &mut [u8]) -> io::Result<()> {
+    let regions = self.regions.lock().unwrap();
+    // Regions start at or before `address`, unless it's the highest address there is.
+    let region = match address.checked_add(1) {
+      Some(end) => regions.range(..end).next_back(),
+      None => regions.iter().next_back()
+    };
+    if let Some((start, data)) = region {
+      let offset = address - start;
+      if offset + buf.len() <= data.len() {
+        buf.copy_from_slice(&data[offset..offset + buf.len()]);
+        return Ok(());
+      }
+    }
+    Err(io::Error::new(io::ErrorKind::UnexpectedEof, format!("no memory at {:#x}", address)))
+  }
+
+  fn base_address(&self, module: &str) -> Option<usize> {
+    self.modules.lock().unwrap().get(module).cloned()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use error::ReaderError;
+  use profile::OffsetProfile;
+  use MemoryEntryReader;
+
+  use byteorder::{ByteOrder, LittleEndian};
+
+  const BASE: usize = 0x1000_0000;
+  const INDEX: usize = 0x4000_0000;
+  const CHAT: usize = 0x5000_0000;
+
+  fn u32_bytes(value: usize) -> Vec<u8> {
+    let mut bytes = vec![0; 4];
+    LittleEndian::write_u32(&mut bytes, value as u32);
+    bytes
+  }
+
+  /// Writes the default profile's pointer chain, ending at the returned address of the chat log.
+  fn write_chain(source: &InMemorySource) -> usize {
+    source.set_module("ffxiv_dx11.exe", BASE);
+    source.write(BASE + 0x017a4138, &u32_bytes(0x2000_0000));
+    source.write(0x2000_0000 + 0x60, &u32_bytes(0x3000_0000));
+    0x3000_0000 + 0x2b8
+  }
+
+  /// Writes a chat log holding `texts`, with the index table and text at fixed addresses.
+  fn write_chat_log(source: &InMemorySource, lines: usize, texts: &[&str]) {
+    let mut index = Vec::new();
+    let mut chat = Vec::new();
+    for (i, text) in texts.iter().enumerate() {
+      chat.extend(u32_bytes(100 + i));
+      chat.extend(&[0x0a, 0, 0, 0, 0x3a]);
+      chat.extend(b"Sender:");
+      chat.extend(text.as_bytes());
+      index.extend(u32_bytes(chat.len()));
+    }
+    source.write(INDEX, &index);
+    source.write(CHAT, &chat);
+    source.write(lines, &u32_bytes(texts.len()));
+    source.write(lines + 40, &u32_bytes(INDEX + index.len()));
+    source.write(lines + 52, &u32_bytes(CHAT));
+  }
+
+  #[test]
+  fn writes_merge_regions() {
+    let source = InMemorySource::new();
+    source.write(0x10, &[1, 2]);
+    source.write(0x14, &[5, 6]);
+    source.write(0x12, &[3, 4]);
+    let mut buf = [0; 6];
+    source.read_into(0x10, &mut buf).unwrap();
+    assert_eq!(buf, [1, 2, 3, 4, 5, 6]);
+    assert!(source.read_into(0x0f, &mut buf).is_err());
+    assert!(source.read_into(0x11, &mut buf).is_err());
+  }
+
+  #[test]
+  fn reads_at_the_highest_address() {
+    let source = InMemorySource::new();
+    source.write(0x10, &[1]);
+    let mut buf = [0; 1];
+    assert!(source.read_into(usize::MAX, &mut buf).is_err());
+    source.write(usize::MAX - 1, &[7]);
+    assert!(source.read_into(usize::MAX, &mut buf).is_err());
+    source.read_into(usize::MAX - 1, &mut buf).unwrap();
+    assert_eq!(buf, [7]);
+  }
+
+  #[test]
+  fn resolves_pointer_chain() {
+    let source = InMemorySource::new();
+    let lines = write_chain(&source);
+    assert_eq!(OffsetProfile::default().resolve(&source).ok(), Some(lines));
+    source.clear();
+    assert!(OffsetProfile::default().resolve(&source).is_err());
+  }
+
+  #[test]
+  fn reads_entries_through_index_table() {
+    let source = InMemorySource::new();
+    let lines = write_chain(&source);
+    write_chat_log(&source, lines, &["one", "two", "three"]);
+    let reader = MemoryEntryReader::with_source(source, true);
+    let entries: Vec<_> = reader.iter().collect();
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[0].timestamp, 100);
+    assert_eq!(entries[2].timestamp, 102);
+  }
+
+  #[test]
+  fn reattaches_once_the_chat_log_can_be_read() {
+    // Without reconnecting, a reader that can't read the chat log doesn't start
+    let reader = MemoryEntryReader::with_source(InMemorySource::new(), true);
+    assert_eq!(reader.iter().count(), 0);
+    let mut reader = MemoryEntryReader::with_source(InMemorySource::new(), true);
+    reader.reconnect = true;
+    let iter = reader.iter();
+    let lines = write_chain(reader.source());
+    write_chat_log(reader.source(), lines, &["one", "two"]);
+    let timestamps: Vec<u32> = iter.map(|e| e.timestamp).collect();
+    assert_eq!(timestamps, vec![100, 101]);
+  }
+
+  #[test]
+  fn reports_why_the_chat_log_can_not_be_read() {
+    let source = InMemorySource::new();
+    match OffsetProfile::default().resolve(&source) {
+      Err(ReaderError::ModuleNotFound(ref m)) if m == "ffxiv_dx11.exe" => {},
+      r => panic!("expected a missing module, got {:?}", r)
+    }
+    source.set_module("ffxiv_dx11.exe", BASE);
+    let reader = MemoryEntryReader::with_source(source, true);
+    let mut iter = reader.iter();
+    assert!(iter.next().is_none());
+    match iter.error() {
+      Some(&ReaderError::InvalidPointerChain) => {},
+      e => panic!("expected an invalid pointer chain, got {:?}", e)
+    }
+  }
+}
@@ -8,6 +8,10 @@ use messages::Parses;
 
 use byteorder::{ByteOrder, LittleEndian};
 
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fmt::Result as FmtResult;
+
 /// A wrapper around the raw bytes of an entry.
 #[derive(Debug)]
 pub struct RawEntry {
@@ -26,25 +30,80 @@ impl RawEntry {
   ///
   /// If the bytes are invalid, this will return `None`.
   pub fn as_parts(&self) -> Option<RawEntryParts> {
-    let header = opt!(self.get_header());
-    let second_colon = opt!(self.bytes[9..].iter().position(|b| b == &0x3a));
-    let sender = self.bytes[9..second_colon + 9].to_vec();
-    let message = self.bytes[second_colon + 9 + 1..].to_vec();
-    Some(RawEntryParts {
+    self.try_parts().ok()
+  }
+
+  /// Converts the bytes in their raw parts, returning why they are invalid if they are.
+  pub fn try_parts(&self) -> Result<RawEntryParts, EntryErrorReason> {
+    // The header is followed by a colon, then the sender
+    if self.bytes.len() < HEADER_LENGTH + 1 {
+      return Err(EntryErrorReason::TooShort(self.bytes.len()));
+    }
+    let header = self.bytes[..HEADER_LENGTH].to_vec();
+    let rest = &self.bytes[HEADER_LENGTH + 1..];
+    let second_colon = match rest.iter().position(|b| b == &0x3a) {
+      Some(i) => i,
+      None => return Err(EntryErrorReason::MissingSenderSeparator)
+    };
+    Ok(RawEntryParts {
       header,
-      sender,
-      message
+      sender: rest[..second_colon].to_vec(),
+      message: rest[second_colon + 1..].to_vec()
     })
   }
 
-  fn get_header(&self) -> Option<Vec<u8>> {
-    if self.bytes.len() < 8 {
-      return None;
+  /// Converts the bytes into a processed entry.
+  ///
+  /// If the bytes are invalid, the error holds them along with the reason.
+  pub fn into_entry(self) -> Result<Entry, EntryError> {
+    match self.try_parts() {
+      Ok(parts) => Ok(parts.as_entry()),
+      Err(reason) => Err(EntryError {
+        bytes: self.bytes,
+        reason
+      })
     }
-    Some(self.bytes[..8].to_vec())
   }
 }
 
+/// The length of the header at the start of each entry.
+const HEADER_LENGTH: usize = 8;
+
+/// An entry that could not be parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EntryError {
+  /// The raw bytes of the entry.
+  pub bytes: Vec<u8>,
+  /// Why the entry could not be parsed.
+  pub reason: EntryErrorReason
+}
+
+/// The reason an entry could not be parsed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EntryErrorReason {
+  /// The entry was too short to hold a header. Holds the length of the entry.
+  TooShort(usize),
+  /// There was no colon separating the sender from the message.
+  MissingSenderSeparator
+}
+
+impl Display for EntryErrorReason {
+  fn fmt(&self, f: &mut Formatter) -> FmtResult {
+    match *self {
+      EntryErrorReason::TooShort(len) => write!(f, "entry is too short ({} bytes)", len),
+      EntryErrorReason::MissingSenderSeparator => write!(f, "entry has no sender separator")
+    }
+  }
+}
+
+impl Display for EntryError {
+  fn fmt(&self, f: &mut Formatter) -> FmtResult {
+    write!(f, "{}: {:?}", self.reason, self.bytes)
+  }
+}
+
+impl Error for EntryError {}
+
 /// The raw parts of an entry.
 #[derive(Debug)]
 pub struct RawEntryParts {
@@ -77,7 +136,8 @@ impl RawEntryParts {
       message_type: message_type.into(),
       timestamp,
       sender,
-      message
+      message,
+      outgoing: false
     }
   }
 }
@@ -92,5 +152,47 @@ pub struct Entry {
   /// The sender of the message, if any.
   pub sender: Option<Part>,
   /// The message of the entry.
-  pub message: Message
+  pub message: Message,
+  /// Whether the entry was sent by the logged-in character.
+  ///
+  /// This is only set by a [`MemoryEntryReader`](../../struct.MemoryEntryReader.html) whose
+  /// profile says where to find the character's name. It is only serialized when set, so entries
+  /// serialized without it are the same as they were before it existed.
+  #[serde(default, skip_serializing_if = "is_false")]
+  pub outgoing: bool
+}
+
+fn is_false(b: &bool) -> bool {
+  !*b
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn splits_entries_into_parts() {
+    let entry = RawEntry::new(b"\x01\x00\x00\x00\x0a\x00\x00\x00:Sender:Hello: there".to_vec());
+    let parts = entry.try_parts().unwrap();
+    assert_eq!(parts.header, b"\x01\x00\x00\x00\x0a\x00\x00\x00");
+    assert_eq!(parts.sender, b"Sender");
+    assert_eq!(parts.message, b"Hello: there");
+    // No sender
+    let parts = RawEntry::new(b"\x01\x00\x00\x00\x0a\x00\x00\x00::Hello".to_vec()).try_parts();
+    assert!(parts.unwrap().sender.is_empty());
+  }
+
+  #[test]
+  fn rejects_bad_entries() {
+    assert_eq!(RawEntry::new(Vec::new()).try_parts().unwrap_err(), EntryErrorReason::TooShort(0));
+    let header_only = b"\x01\x00\x00\x00\x0a\x00\x00\x00".to_vec();
+    assert_eq!(RawEntry::new(header_only).try_parts().unwrap_err(), EntryErrorReason::TooShort(8));
+    let no_separator = b"\x01\x00\x00\x00\x0a\x00\x00\x00:Sender".to_vec();
+    let error = RawEntry::new(no_separator.clone()).into_entry().unwrap_err();
+    assert_eq!(error, EntryError {
+      bytes: no_separator,
+      reason: EntryErrorReason::MissingSenderSeparator
+    });
+    assert!(RawEntry::new(b"short".to_vec()).as_parts().is_none());
+  }
 }
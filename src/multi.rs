@@ -0,0 +1,301 @@
+//! Reading several game clients at once
+
+use channel::{self, EventReceiver, EventSender, OverflowPolicy};
+use error::ReaderError;
+use events::ReaderEvent;
+use memory::MemorySource;
+use messages::entries::Entry;
+use player::LocalPlayer;
+use poll::PollInterval;
+use process;
+use profile::OffsetProfile;
+use registry::ProfileRegistry;
+use source::EntrySource;
+use MemoryEntryReader;
+
+use std::fmt::{Display, Formatter};
+use std::fmt::Result as FmtResult;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+/// Identifies the game client an entry was read from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ClientId {
+  /// The PID of the client's process.
+  pub pid: u32,
+  /// The name of the character logged in on the client, if it could be read.
+  ///
+  /// This is only read if the reader's profile has a
+  /// [`character_name`](../profile/struct.OffsetProfile.html#structfield.character_name). The
+  /// default profile doesn't, so this is always `None` unless one is set.
+  pub character: Option<String>
+}
+
+impl Display for ClientId {
+  fn fmt(&self, f: &mut Formatter) -> FmtResult {
+    match self.character {
+      Some(ref name) => write!(f, "{} ({})", self.pid, name),
+      None => write!(f, "{}", self.pid)
+    }
+  }
+}
+
+/// An entry tagged with the client it was read from.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClientEntry {
+  /// The client the entry was read from.
+  pub client: ClientId,
+  /// The entry.
+  pub entry: Entry
+}
+
+/// A reader that reads from several game clients at once, merging their entries.
+///
+/// A [`MemoryEntryReader`](../struct.MemoryEntryReader.html) is started for each client, using the
+/// settings of this reader. Entries are sent in the order they are read, tagged with the client
+/// they came from.
+///
+/// Status events from every client are passed on as they are. Errors are wrapped in
+/// `ReaderError::Client` with the PID of the client they came from.
+///
+/// ```rust,no_run
+/// # use ffxiv_reader::multi::MultiClientReader;
+/// # use ffxiv_reader::process::GAME_PROCESS_NAME;
+/// let reader = MultiClientReader::from_process_name(GAME_PROCESS_NAME, false);
+/// for tagged in reader.iter() {
+///   println!("{}: {:?}", tagged.client, tagged.entry);
+/// }
+/// ```
+pub struct MultiClientReader {
+  /// Whether each client's reader will stop once it runs out of messages.
+  pub stop: bool,
+  /// Whether each client's reader waits for its memory to become readable again.
+  pub reconnect: bool,
+  /// The offsets used to find the chat log in each client's memory.
+  pub profile: OffsetProfile,
+  /// Profiles for known versions of the game, used instead of `profile` if set.
+  ///
+  /// This is the built-in registry by default, like for a single client.
+  pub registry: Option<ProfileRegistry>,
+  /// The number of entries that can be waiting to be received before `overflow` applies.
+  pub capacity: Option<usize>,
+  /// What to do with new entries when `capacity` entries are waiting to be received.
+  pub overflow: OverflowPolicy,
+  /// How long each client's reader waits before checking for new messages after finding none.
+  pub poll: PollInterval,
+  pids: Vec<u32>,
+  readers: Mutex<Vec<Box<dyn EntrySource + Send>>>,
+  /// Cleared by `stop`, so threads passing on events stop waiting for room in a full channel
+  run: Arc<AtomicBool>
+}
+
+impl MultiClientReader {
+  /// Create a new reader for the processes using the PIDs in `pids`.
+  pub fn new(pids: Vec<u32>, stop: bool) -> Self {
+    MultiClientReader {
+      stop,
+      reconnect: false,
+      profile: OffsetProfile::default(),
+      registry: ProfileRegistry::builtin(),
+      capacity: None,
+      overflow: OverflowPolicy::Block,
+      poll: PollInterval::default(),
+      pids,
+      readers: Mutex::new(Vec::new()),
+      run: Arc::new(AtomicBool::new(false))
+    }
+  }
+
+  /// Create a new reader for every running process matching `name`.
+  ///
+  /// Processes are only looked for now. Clients started later are not read.
+  pub fn from_process_name(name: &str, stop: bool) -> Self {
+    let processes = process::find_processes(name).unwrap_or_default();
+    MultiClientReader::new(processes.into_iter().map(|p| p.pid).collect(), stop)
+  }
+
+  /// The PIDs of the clients this reader reads from.
+  pub fn pids(&self) -> &[u32] {
+    &self.pids
+  }
+
+  /// Starts reading from every client.
+  ///
+  /// Clients that can't be read are reported with an `Error` event, and the rest are still read.
+  /// This only fails if the reader has already been started, if there are no clients, or if none
+  /// of them can be read.
+  pub fn start(&self) -> Result<EventReceiver<ClientEntry>, ReaderError> {
+    self.start_with(|pid| MemoryEntryReader::new(pid, self.stop))
+  }
+
+  /// Starts reading from every client, using `open` to create the reader for each PID.
+  fn start_with<S, F>(&self, open: F) -> Result<EventReceiver<ClientEntry>, ReaderError>
+    where S: MemorySource + Send + Sync + 'static,
+          F: Fn(u32) -> MemoryEntryReader<S>
+  {
+    let mut readers = self.readers.lock().unwrap();
+    if !readers.is_empty() {
+      return Err(ReaderError::AlreadyStarted);
+    }
+    // Check the interval once here rather than failing for every client
+    self.poll.timer()?;
+    let (mut tx, rx) = channel::channel(self.capacity, self.overflow);
+    self.run.store(true, Ordering::Relaxed);
+    tx.stop_with(self.run.clone());
+    let mut first_error = None;
+    for &pid in &self.pids {
+      let mut reader = open(pid);
+      reader.reconnect = self.reconnect;
+      reader.profile = self.profile.clone();
+      reader.registry = self.registry.clone();
+      reader.poll = self.poll;
+      match reader.start() {
+        Ok(client_rx) => {
+          let local_player = reader.local_player.clone();
+          let tx = tx.clone();
+          thread::spawn(move || forward(pid, local_player, client_rx, tx));
+          readers.push(Box::new(reader));
+        },
+        Err(e) => {
+          let e = ReaderError::Client(pid, Box::new(e));
+          match first_error {
+            None => first_error = Some(e),
+            Some(_) => { tx.send(ReaderEvent::Error(e)).ok(); }
+          }
+        }
+      }
+    }
+    match first_error {
+      Some(e) if readers.is_empty() => Err(e),
+      Some(e) => {
+        tx.send(ReaderEvent::Error(e)).ok();
+        Ok(rx)
+      },
+      None if readers.is_empty() => Err(ReaderError::ProcessNotFound),
+      None => Ok(rx)
+    }
+  }
+
+  /// Stops reading from every client.
+  ///
+  /// This is called automatically when the reader is dropped.
+  pub fn stop(&self) {
+    self.run.store(false, Ordering::Relaxed);
+    self.readers.lock().unwrap().clear();
+  }
+
+  /// Creates an iterator over the entries from every client.
+  ///
+  /// This automatically calls `start`. Status events are skipped.
+  pub fn iter(&self) -> MultiClientReaderIterator {
+    match self.start() {
+      Ok(rx) => MultiClientReaderIterator { rx: Some(rx), error: None },
+      Err(e) => MultiClientReaderIterator { rx: None, error: Some(e) }
+    }
+  }
+}
+
+impl Drop for MultiClientReader {
+  fn drop(&mut self) {
+    self.stop();
+  }
+}
+
+/// Passes on the events from one client, tagging its entries with the character the client's
+/// reader last read.
+fn forward(pid: u32,
+           local_player: Arc<Mutex<Option<LocalPlayer>>>,
+           rx: EventReceiver<Entry>,
+           tx: EventSender<ClientEntry>) {
+  for event in rx {
+    let client = ClientId {
+      pid,
+      character: local_player.lock().unwrap().as_ref().map(|p| p.name.clone())
+    };
+    let event = match event {
+      ReaderEvent::Item(entry) => ReaderEvent::Item(ClientEntry { client, entry }),
+      ReaderEvent::Error(e) => ReaderEvent::Error(ReaderError::Client(pid, Box::new(e))),
+      ReaderEvent::Detached => ReaderEvent::Detached,
+      ReaderEvent::Attached => ReaderEvent::Attached,
+      ReaderEvent::Gap(n) => ReaderEvent::Gap(n),
+      ReaderEvent::Dropped(n) => ReaderEvent::Dropped(n)
+    };
+    try_or!(tx.send(event), break);
+  }
+}
+
+/// The iterator for [`MultiClientReader`](struct.MultiClientReader.html).
+///
+/// The last error encountered, including any error from starting the reader, can be checked with
+/// `error()`.
+pub struct MultiClientReaderIterator {
+  rx: Option<EventReceiver<ClientEntry>>,
+  error: Option<ReaderError>
+}
+
+impl MultiClientReaderIterator {
+  /// Gets the last error encountered by the reader, if any.
+  pub fn error(&self) -> Option<&ReaderError> {
+    self.error.as_ref()
+  }
+}
+
+impl Iterator for MultiClientReaderIterator {
+  type Item = ClientEntry;
+
+  fn next(&mut self) -> Option<ClientEntry> {
+    let rx = match self.rx {
+      Some(ref r) => r,
+      None => return None
+    };
+    loop {
+      match rx.recv() {
+        Ok(ReaderEvent::Item(entry)) => return Some(entry),
+        Ok(ReaderEvent::Error(e)) => self.error = Some(e),
+        Ok(_) => continue,
+        Err(_) => return None
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use messages::HasDisplayText;
+  use testing;
+
+  #[test]
+  fn tags_entries_with_their_client() {
+    let reader = MultiClientReader::new(vec![1, 2], true);
+    let rx = reader.start_with(|pid| {
+      let source = testing::game();
+      let lines = [testing::line(100, "A", &format!("first from {}", pid)),
+                   testing::line(101, "B", &format!("second from {}", pid))];
+      testing::write_lines(&source, &lines);
+      MemoryEntryReader::with_source(source, true)
+    }).unwrap();
+    let mut tagged: Vec<(u32, Option<String>, String)> = rx.iter()
+      .filter_map(ReaderEvent::item)
+      .map(|e| (e.client.pid, e.client.character, e.entry.message.display_text()))
+      .collect();
+    // Clients are read at the same time, so only each client's own entries are in order
+    tagged.sort_by_key(|&(pid, _, _)| pid);
+    assert_eq!(tagged, vec![
+      (1, None, "first from 1".to_string()),
+      (1, None, "second from 1".to_string()),
+      (2, None, "first from 2".to_string()),
+      (2, None, "second from 2".to_string())
+    ]);
+  }
+
+  #[test]
+  fn fails_without_clients() {
+    let reader = MultiClientReader::new(Vec::new(), true);
+    match reader.start_with(|_| MemoryEntryReader::with_source(testing::game(), true)) {
+      Err(ReaderError::ProcessNotFound) => {},
+      r => panic!("expected ProcessNotFound, got {:?}", r.err())
+    }
+  }
+}
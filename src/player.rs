@@ -0,0 +1,99 @@
+//! The character logged in to the game
+
+use memory::MemorySource;
+use messages::HasDisplayText;
+use messages::entries::Entry;
+use messages::parts::Part;
+use profile::OffsetProfile;
+
+/// The character logged in to the game, read from memory.
+///
+/// Where to find it is given by the `character_name` and `home_world` of an
+/// [`OffsetProfile`](../profile/struct.OffsetProfile.html).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct LocalPlayer {
+  /// The name of the character.
+  pub name: String,
+  /// The name of the character's home world, if it could be read.
+  pub world: Option<String>
+}
+
+impl LocalPlayer {
+  /// Reads the logged-in character from `source` using `profile`.
+  ///
+  /// Returns `None` if the profile doesn't say where the character's name is, or if it can't be
+  /// read.
+  pub fn read<S: MemorySource>(source: &S, profile: &OffsetProfile) -> Option<Self> {
+    let name = opt!(profile.read_character_name(source));
+    let world = profile.home_world.as_ref().and_then(|w| w.read(source, profile));
+    Some(LocalPlayer { name, world })
+  }
+
+  /// Checks if `entry` was sent by this character.
+  ///
+  /// The sender matches if it is this character's name, optionally followed by its home world as
+  /// shown for players from other worlds. Icons in the sender, such as party numbers, are ignored.
+  pub fn is_sender(&self, entry: &Entry) -> bool {
+    let sender = match entry.sender {
+      Some(Part::Name { ref real_name, .. }) => real_name.display_text(),
+      Some(Part::PlainText(ref text)) => text.clone(),
+      _ => return false
+    };
+    let sender = sender.trim_start_matches(is_icon);
+    if !sender.starts_with(&self.name[..]) {
+      return false;
+    }
+    let rest = sender[self.name.len()..].trim_start_matches(is_icon);
+    rest.is_empty() || self.world.as_ref().map(|w| w == rest).unwrap_or(false)
+  }
+}
+
+/// Checks if `c` is one of the game's icons, which are in the private use area.
+fn is_icon(c: char) -> bool {
+  ('\u{e000}'..='\u{f8ff}').contains(&c)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use testing;
+
+  fn player() -> LocalPlayer {
+    LocalPlayer { name: "Tataru Taru".to_string(), world: Some("Gilgamesh".to_string()) }
+  }
+
+  fn entry(sender: &str) -> Entry {
+    ::parse_entry(testing::line(100, sender, "hello"), Some(&player())).unwrap()
+  }
+
+  #[test]
+  fn matches_plain_text_senders() {
+    let entry = entry("Tataru Taru");
+    assert!(player().is_sender(&entry));
+    assert!(entry.outgoing);
+  }
+
+  #[test]
+  fn ignores_icons_in_senders() {
+    let entry = entry("\u{e090}Tataru Taru\u{e05d}");
+    assert!(player().is_sender(&entry));
+    assert!(entry.outgoing);
+  }
+
+  #[test]
+  fn matches_senders_with_the_home_world() {
+    assert!(entry("Tataru Taru\u{e05d}Gilgamesh").outgoing);
+    assert!(!entry("Tataru Taru\u{e05d}Balmung").outgoing);
+    let player = LocalPlayer { world: None, ..player() };
+    assert!(!player.is_sender(&entry("Tataru Taru\u{e05d}Gilgamesh")));
+  }
+
+  #[test]
+  fn rejects_other_senders() {
+    assert!(!entry("Tataru Tarutaru").outgoing);
+    assert!(!entry("Krile Baldesion").outgoing);
+    assert!(!entry("").outgoing);
+    let not_outgoing = ::parse_entry(testing::line(100, "Tataru Taru", "hello"), None).unwrap();
+    assert!(!not_outgoing.outgoing);
+  }
+}
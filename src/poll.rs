@@ -0,0 +1,129 @@
+//! How often readers check for new entries
+//!
+//! A reader that has caught up waits before checking again. With a fixed interval it always waits
+//! the same time. With an adaptive interval it checks again quickly while entries keep arriving,
+//! such as during combat, and waits longer the longer nothing new is found.
+
+use error::ReaderError;
+
+use std::time::Duration;
+
+/// The shortest wait of an adaptive interval, so a `min` of zero doesn't check in a busy loop.
+pub const MIN_WAIT: Duration = Duration::from_millis(1);
+
+/// How long a reader waits before checking for new entries after finding none.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PollInterval {
+  /// Always wait the same time.
+  Fixed(Duration),
+  /// Wait `min` after new entries were last found, doubling the wait after every check that finds
+  /// nothing, up to `max`.
+  ///
+  /// `min` can't be longer than `max`, and waits shorter than `MIN_WAIT` are raised to it.
+  Adaptive {
+    /// The shortest wait, used right after new entries are found.
+    min: Duration,
+    /// The longest wait, used once nothing has been found for a while.
+    max: Duration
+  }
+}
+
+impl Default for PollInterval {
+  fn default() -> Self {
+    PollInterval::Fixed(Duration::from_millis(100))
+  }
+}
+
+impl PollInterval {
+  /// Creates a timer that tracks how long to wait with this interval.
+  ///
+  /// Fails with `InvalidPollInterval` if this is an adaptive interval whose `min` is longer than
+  /// its `max`.
+  pub fn timer(&self) -> Result<PollTimer, ReaderError> {
+    let interval = match *self {
+      PollInterval::Adaptive { min, max } if min > max => {
+        return Err(ReaderError::InvalidPollInterval);
+      },
+      PollInterval::Adaptive { min, max } => {
+        PollInterval::Adaptive { min: min.max(MIN_WAIT), max: max.max(MIN_WAIT) }
+      },
+      fixed => fixed
+    };
+    Ok(PollTimer {
+      interval,
+      wait: None
+    })
+  }
+}
+
+/// Tracks how long to wait between checks for new entries.
+#[derive(Debug, Clone)]
+pub struct PollTimer {
+  interval: PollInterval,
+  /// The last wait, if nothing has been found since
+  wait: Option<Duration>
+}
+
+impl PollTimer {
+  /// Notes that a check found new entries.
+  pub fn found(&mut self) {
+    self.wait = None;
+  }
+
+  /// Notes that a check found nothing, returning how long to wait before the next one.
+  pub fn idle(&mut self) -> Duration {
+    let wait = match self.interval {
+      PollInterval::Fixed(interval) => interval,
+      PollInterval::Adaptive { min, max } => match self.wait {
+        Some(last) => last.checked_mul(2).unwrap_or(max).min(max),
+        None => min
+      }
+    };
+    self.wait = Some(wait);
+    wait
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn ms(millis: u64) -> Duration {
+    Duration::from_millis(millis)
+  }
+
+  #[test]
+  fn backs_off_to_max() {
+    let mut timer = PollInterval::Adaptive { min: ms(10), max: ms(50) }.timer().unwrap();
+    let waits: Vec<Duration> = (0..5).map(|_| timer.idle()).collect();
+    assert_eq!(waits, vec![ms(10), ms(20), ms(40), ms(50), ms(50)]);
+    timer.found();
+    assert_eq!(timer.idle(), ms(10));
+  }
+
+  #[test]
+  fn raises_zero_waits() {
+    let mut timer = PollInterval::Adaptive { min: ms(0), max: ms(4) }.timer().unwrap();
+    let waits: Vec<Duration> = (0..4).map(|_| timer.idle()).collect();
+    assert_eq!(waits, vec![ms(1), ms(2), ms(4), ms(4)]);
+    let mut timer = PollInterval::Adaptive { min: ms(0), max: ms(0) }.timer().unwrap();
+    assert_eq!(timer.idle(), MIN_WAIT);
+    assert_eq!(timer.idle(), MIN_WAIT);
+  }
+
+  #[test]
+  fn rejects_min_above_max() {
+    let interval = PollInterval::Adaptive { min: ms(50), max: ms(10) };
+    match interval.timer() {
+      Err(ReaderError::InvalidPollInterval) => {},
+      r => panic!("expected an invalid interval, got {:?}", r)
+    }
+  }
+
+  #[test]
+  fn keeps_fixed_waits() {
+    let mut timer = PollInterval::default().timer().unwrap();
+    assert_eq!(timer.idle(), ms(100));
+    assert_eq!(timer.idle(), ms(100));
+  }
+}
@@ -0,0 +1,232 @@
+//! Finding game processes
+//!
+//! Processes are found by reading `/proc`, so nothing will be found on systems without it. Every
+//! function has an `_in` variant that reads from a different root directory laid out like `/proc`.
+
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// The name of the game's process.
+pub const GAME_PROCESS_NAME: &'static str = "ffxiv_dx11.exe";
+
+/// The length that the kernel truncates process names to in `comm`.
+const COMM_LENGTH: usize = 15;
+
+/// A running process that may be the game.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessCandidate {
+  /// The PID of the process.
+  pub pid: u32,
+  /// The name of the process, as reported by the kernel.
+  pub comm: String,
+  /// The path to the executable of the process, if it can be read.
+  ///
+  /// Under Wine or Proton, this is the path to the Wine loader rather than the game.
+  pub exe: Option<PathBuf>,
+  /// The command line of the process.
+  pub cmdline: Vec<String>,
+  /// The time the process started, in clock ticks after boot.
+  pub start_time: u64
+}
+
+impl ProcessCandidate {
+  /// Checks if this process is the executable named `name`.
+  ///
+  /// The process matches if its `comm`, the file name of its executable, or the file name of the
+  /// first argument of its command line is `name`. Windows paths are handled so that processes
+  /// running under Wine or Proton match, and those comparisons ignore case.
+  pub fn matches(&self, name: &str) -> bool {
+    let truncated: String = name.chars().take(COMM_LENGTH).collect();
+    if self.comm == name || (name.len() > COMM_LENGTH && self.comm == truncated) {
+      return true;
+    }
+    let exe_name = self.exe.as_ref().and_then(|e| e.file_name()).and_then(|n| n.to_str());
+    if exe_name == Some(name) {
+      return true;
+    }
+    let lower_name = name.to_lowercase();
+    self.cmdline.first()
+      .and_then(|arg| arg.rsplit(&['/', '\\'][..]).next())
+      .map(|file_name| file_name.to_lowercase() == lower_name)
+      .unwrap_or(false)
+  }
+}
+
+/// Reads information about the process using PID `pid`, if it is running.
+pub fn process(pid: u32) -> Option<ProcessCandidate> {
+  process_in("/proc", pid)
+}
+
+/// Reads information about the process using PID `pid` from `root`.
+pub fn process_in<P: AsRef<Path>>(root: P, pid: u32) -> Option<ProcessCandidate> {
+  let dir = root.as_ref().join(pid.to_string());
+  let comm = opt!(read_file(&dir.join("comm")).ok());
+  let stat = opt!(read_file(&dir.join("stat")).ok());
+  let cmdline = read_file(&dir.join("cmdline")).unwrap_or_default();
+  Some(ProcessCandidate {
+    pid,
+    comm: comm.trim().to_owned(),
+    exe: fs::read_link(dir.join("exe")).ok(),
+    cmdline: cmdline.split('\0').filter(|x| !x.is_empty()).map(ToOwned::to_owned).collect(),
+    start_time: opt!(parse_start_time(&stat))
+  })
+}
+
+/// Gets the name of the process using PID `pid`, if it is running.
+pub fn process_name(pid: u32) -> Option<String> {
+  process(pid).map(|p| p.comm)
+}
+
+/// Finds all running processes that match `name`.
+///
+/// The newest process is first. See
+/// [`ProcessCandidate::matches`](struct.ProcessCandidate.html#method.matches) for how processes are
+/// matched.
+pub fn find_processes(name: &str) -> io::Result<Vec<ProcessCandidate>> {
+  find_processes_in("/proc", name)
+}
+
+/// Finds all processes in `root` that match `name`.
+pub fn find_processes_in<P: AsRef<Path>>(root: P, name: &str) -> io::Result<Vec<ProcessCandidate>> {
+  let root = root.as_ref();
+  let mut candidates: Vec<ProcessCandidate> = fs::read_dir(root)?
+    .filter_map(|e| e.ok())
+    .filter_map(|e| e.file_name().to_str().and_then(|x| x.parse().ok()))
+    .filter_map(|pid| process_in(root, pid))
+    .filter(|p| p.matches(name))
+    .collect();
+  candidates.sort_by(|a, b| b.start_time.cmp(&a.start_time).then(b.pid.cmp(&a.pid)));
+  Ok(candidates)
+}
+
+/// Finds the PID of the newest running process that matches `name`.
+pub fn find_pid(name: &str) -> Option<u32> {
+  find_processes(name).ok().and_then(|c| c.first().map(|p| p.pid))
+}
+
+/// Gets the ranges of readable memory of the process using PID `pid`, as start and end addresses.
+pub fn readable_regions(pid: u32) -> io::Result<Vec<(usize, usize)>> {
+  readable_regions_in("/proc", pid)
+}
+
+/// Gets the ranges of readable memory of the process using PID `pid` from `root`.
+///
+/// The ranges are read from the process's `maps` file, in the order they are listed.
+pub fn readable_regions_in<P: AsRef<Path>>(root: P, pid: u32) -> io::Result<Vec<(usize, usize)>> {
+  let maps = read_file(&root.as_ref().join(pid.to_string()).join("maps"))?;
+  Ok(maps.lines().filter_map(parse_map_line).collect())
+}
+
+fn read_file(path: &Path) -> io::Result<String> {
+  let mut content = String::new();
+  File::open(path)?.read_to_string(&mut content)?;
+  Ok(content)
+}
+
+fn parse_start_time(stat: &str) -> Option<u64> {
+  // The name can contain spaces and parentheses, so start after the last closing parenthesis. The
+  // fields after it start at the third field, and the start time is the twenty-second.
+  let rest = &stat[opt!(stat.rfind(')')) + 1..];
+  rest.split_whitespace().nth(19).and_then(|x| x.parse().ok())
+}
+
+fn parse_map_line(line: &str) -> Option<(usize, usize)> {
+  // Lines look like "00400000-00452000 r-xp 00000000 08:02 173521 /usr/bin/dbus-daemon"
+  let mut fields = line.split_whitespace();
+  let mut range = opt!(fields.next()).split('-');
+  let start = opt!(range.next().and_then(|x| usize::from_str_radix(x, 16).ok()));
+  let end = opt!(range.next().and_then(|x| usize::from_str_radix(x, 16).ok()));
+  if !opt!(fields.next()).starts_with('r') || end <= start {
+    return None;
+  }
+  Some((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::env;
+  use std::io::Write;
+  #[cfg(unix)]
+  use std::os::unix::fs::symlink;
+
+  /// Creates an empty directory to lay out like `/proc`, unique to the test named `name`.
+  fn fake_proc(name: &str) -> PathBuf {
+    let root = env::temp_dir().join(format!("ffxiv_reader_proc_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    root
+  }
+
+  /// Adds a process to a fake `/proc`. `cmdline` is separated by null bytes like the real file.
+  fn add_process(root: &Path, pid: u32, comm: &str, cmdline: &[&str], start_time: u64) {
+    let dir = root.join(pid.to_string());
+    fs::create_dir_all(&dir).unwrap();
+    let stat = format!("{} ({}) S{} {} 0 0", pid, comm, " 1".repeat(18), start_time);
+    File::create(dir.join("stat")).unwrap().write_all(stat.as_bytes()).unwrap();
+    File::create(dir.join("comm")).unwrap().write_all(format!("{}\n", comm).as_bytes()).unwrap();
+    let cmdline: String = cmdline.iter().map(|arg| format!("{}\0", arg)).collect();
+    File::create(dir.join("cmdline")).unwrap().write_all(cmdline.as_bytes()).unwrap();
+  }
+
+  #[test]
+  fn parses_start_time_after_odd_names() {
+    let fields = " 1".repeat(18);
+    assert_eq!(parse_start_time(&format!("12 (ffxiv_dx11.exe) S{} 500", fields)), Some(500));
+    assert_eq!(parse_start_time(&format!("12 (a) b (c)) S{} 501 0", fields)), Some(501));
+    assert_eq!(parse_start_time(&format!("12 (two words) S{} 502", fields)), Some(502));
+    assert_eq!(parse_start_time("12 (short) S 1 2"), None);
+    assert_eq!(parse_start_time("garbage"), None);
+  }
+
+  #[test]
+  #[cfg(unix)]
+  fn reads_processes() {
+    let root = fake_proc("reads");
+    add_process(&root, 100, "ffxiv_dx11.exe", &["ffxiv_dx11.exe", "DEV.TestSID=0"], 10);
+    symlink("/opt/ffxiv/ffxiv_dx11.exe", root.join("100").join("exe")).unwrap();
+    let p = process_in(&root, 100).unwrap();
+    assert_eq!(p.comm, "ffxiv_dx11.exe");
+    assert_eq!(p.cmdline, vec!["ffxiv_dx11.exe", "DEV.TestSID=0"]);
+    assert_eq!(p.exe, Some(PathBuf::from("/opt/ffxiv/ffxiv_dx11.exe")));
+    assert_eq!(p.start_time, 10);
+    assert!(process_in(&root, 101).is_none());
+    fs::remove_dir_all(&root).unwrap();
+  }
+
+  #[test]
+  #[cfg(unix)]
+  fn finds_wine_and_proton_processes() {
+    let root = fake_proc("wine");
+    // Native
+    add_process(&root, 100, "ffxiv_dx11.exe", &["ffxiv_dx11.exe"], 10);
+    // Wine, with a Windows path and the loader as the executable
+    add_process(&root, 200, "wine64-preload", &["C:\\Program Files\\FFXIV\\game\\FFXIV_DX11.EXE"],
+                30);
+    symlink("/usr/bin/wine64-preloader", root.join("200").join("exe")).unwrap();
+    // Proton, with a Unix path
+    add_process(&root, 300, "ffxiv_dx11.exe", &["/steam/FINAL FANTASY XIV/game/ffxiv_dx11.exe"],
+                20);
+    // Unrelated processes, including one whose arguments mention the game
+    add_process(&root, 400, "bash", &["bash", "ffxiv_dx11.exe"], 40);
+    add_process(&root, 500, "ffxiv_dx11.exe.bak", &["ffxiv_dx11.exe.bak"], 50);
+    fs::create_dir_all(root.join("self")).unwrap();
+    let found: Vec<u32> = find_processes_in(&root, GAME_PROCESS_NAME).unwrap()
+      .iter()
+      .map(|p| p.pid)
+      .collect();
+    assert_eq!(found, vec![200, 300, 100]);
+    fs::remove_dir_all(&root).unwrap();
+  }
+
+  #[test]
+  fn matches_truncated_comm() {
+    let root = fake_proc("comm");
+    add_process(&root, 100, "a_very_long_gam", &[], 10);
+    assert!(process_in(&root, 100).unwrap().matches("a_very_long_game.exe"));
+    assert!(!process_in(&root, 100).unwrap().matches("a_very_long_gun.exe"));
+    fs::remove_dir_all(&root).unwrap();
+  }
+}
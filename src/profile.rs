@@ -0,0 +1,320 @@
+//! Offset profiles describing where the chat log is in memory
+
+use byteorder::{ByteOrder, LittleEndian};
+use serde_json;
+
+use memory::MemorySource;
+use signature::{self, Signature};
+use error::ReaderError;
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// The locations of the chat log structures in memory for one version of the game.
+///
+/// Profiles can be loaded from JSON files, so offsets can be updated after a game patch without
+/// rebuilding the crate. All numbers are decimal, as JSON has no hexadecimal literals.
+///
+/// ```json
+/// {
+///   "module": "ffxiv_dx11.exe",
+///   "pointer_chain": [24789304, 96, 696],
+///   "line_count_offset": 0,
+///   "index_pointer_offset": 40,
+///   "chat_pointer_offset": 52,
+///   "pointer_width": "u32",
+///   "index_width": "u32"
+/// }
+/// ```
+///
+/// A profile may also contain a [`SignatureProfile`](struct.SignatureProfile.html), which is used
+/// to find the start of the pointer chain by scanning the module instead of using the first
+/// offset.
+///
+/// The default profile contains the offsets this crate was written against.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OffsetProfile {
+  /// The name of the module the pointer chain starts from.
+  pub module: String,
+  /// The offsets to follow to find the chat log structure.
+  ///
+  /// The first offset is added to the base address of the module. For every offset after that,
+  /// the pointer at the current address is read and the offset is added to it.
+  pub pointer_chain: Vec<usize>,
+  /// The offset of the total line count in the chat log structure.
+  pub line_count_offset: usize,
+  /// The offset of the pointer to the end of the index table in the chat log structure.
+  pub index_pointer_offset: usize,
+  /// The offset of the pointer to the start of the chat buffer in the chat log structure.
+  pub chat_pointer_offset: usize,
+  /// The width of every pointer read, including those in the pointer chain.
+  #[serde(default)]
+  pub pointer_width: Width,
+  /// The width of each entry in the index table.
+  #[serde(default)]
+  pub index_width: Width,
+  /// A signature used to find the start of the pointer chain, if any.
+  #[serde(default)]
+  pub signature: Option<SignatureProfile>,
+  /// Where to find the name of the logged-in character, if known.
+  #[serde(default)]
+  pub character_name: Option<StringProfile>,
+  /// Where to find the name of the logged-in character's home world, if known.
+  #[serde(default)]
+  pub home_world: Option<StringProfile>
+}
+
+/// The width of an integer in memory.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Width {
+  /// A 32-bit integer.
+  #[default]
+  U32,
+  /// A 64-bit integer.
+  U64
+}
+
+impl Width {
+  /// The number of bytes an integer of this width takes up.
+  pub fn size(&self) -> usize {
+    match *self {
+      Width::U32 => 4,
+      Width::U64 => 8
+    }
+  }
+
+  /// Decodes a little-endian integer of this width from the start of `bytes`.
+  pub fn decode(&self, bytes: &[u8]) -> usize {
+    match *self {
+      Width::U32 => LittleEndian::read_u32(bytes) as usize,
+      Width::U64 => LittleEndian::read_u64(bytes) as usize
+    }
+  }
+
+  /// Reads an integer of this width from `source` at `address`.
+  pub fn read<S: MemorySource>(&self, source: &S, address: usize) -> io::Result<usize> {
+    source.read(address, self.size()).map(|b| self.decode(&b))
+  }
+}
+
+/// A signature that locates the start of a pointer chain in a module image.
+///
+/// The pattern should match an instruction that references the first pointer of the chain using
+/// RIP-relative addressing, such as `mov rax, [rip + disp32]`.
+///
+/// ```json
+/// {
+///   "pattern": "48 8B 05 ?? ?? ?? ?? 48 85 C0 74 ?? 48 8B 88",
+///   "displacement_offset": 3,
+///   "instruction_end": 7
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SignatureProfile {
+  /// The pattern to scan for. See [`Signature`](../signature/struct.Signature.html).
+  pub pattern: String,
+  /// The offset of the 32-bit displacement from the start of the match.
+  pub displacement_offset: usize,
+  /// The offset of the end of the referencing instruction from the start of the match.
+  pub instruction_end: usize
+}
+
+impl SignatureProfile {
+  /// Scans `image`, loaded at `image_base`, for the pattern and resolves the address it references.
+  ///
+  /// Returns `None` if the pattern is invalid or doesn't match.
+  pub fn scan(&self, image: &[u8], image_base: usize) -> Option<usize> {
+    let pattern = opt!(Signature::parse(&self.pattern));
+    let found = opt!(pattern.find(image));
+    signature::rip_relative(image,
+                            image_base,
+                            found + self.displacement_offset,
+                            found + self.instruction_end)
+  }
+}
+
+/// The location of a string in memory, found by following a pointer chain from the base of the
+/// profile's module.
+///
+/// ```json
+/// {
+///   "pointer_chain": [24789304, 48],
+///   "max_length": 32
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StringProfile {
+  /// The offsets to follow to find the string, in the same way as the profile's pointer chain.
+  pub pointer_chain: Vec<usize>,
+  /// The most bytes to read. The string ends at the first null byte within them.
+  #[serde(default = "default_max_length")]
+  pub max_length: usize
+}
+
+fn default_max_length() -> usize {
+  32
+}
+
+impl StringProfile {
+  /// Reads the string using `profile`'s module and pointer width.
+  ///
+  /// Returns `None` if the string can't be read or is empty. Invalid UTF-8 is replaced.
+  pub fn read<S: MemorySource>(&self, source: &S, profile: &OffsetProfile) -> Option<String> {
+    let base_address = opt!(source.base_address(&profile.module));
+    let (first, rest) = opt!(self.pointer_chain.split_first());
+    let address = opt!(profile.follow(source, base_address + first, rest).ok());
+    let bytes = opt!(source.read(address, self.max_length).ok());
+    let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    if end == 0 {
+      return None;
+    }
+    Some(String::from_utf8_lossy(&bytes[..end]).into_owned())
+  }
+}
+
+impl Default for OffsetProfile {
+  fn default() -> Self {
+    OffsetProfile {
+      module: String::from("ffxiv_dx11.exe"),
+      pointer_chain: vec![0x017a4138, 0x60, 0x2b8],
+      line_count_offset: 0,
+      index_pointer_offset: 40,
+      chat_pointer_offset: 52,
+      pointer_width: Width::U32,
+      index_width: Width::U32,
+      signature: None,
+      character_name: None,
+      home_world: None
+    }
+  }
+}
+
+impl OffsetProfile {
+  /// Reads a profile from JSON.
+  pub fn from_reader<R: Read>(reader: R) -> io::Result<Self> {
+    serde_json::from_reader(reader).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+  }
+
+  /// Reads a profile from a JSON file.
+  pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+    OffsetProfile::from_reader(File::open(path)?)
+  }
+
+  /// Follows the pointer chain from the base of the module, returning the address of the chat log
+  /// structure.
+  ///
+  /// If the profile has a signature and it matches the module image, the address it references is
+  /// used in place of the first offset of the chain. Otherwise, the chain is used as-is.
+  ///
+  /// Fails with `ModuleNotFound` if the module is not loaded, and with `InvalidPointerChain` if the
+  /// chain is empty or any pointer in it can't be read or is null.
+  pub fn resolve<S: MemorySource>(&self, source: &S) -> Result<usize, ReaderError> {
+    let (first, rest) = match self.pointer_chain.split_first() {
+      Some(x) => x,
+      None => return Err(ReaderError::InvalidPointerChain)
+    };
+    let base_address = match source.base_address(&self.module) {
+      Some(b) => b,
+      None => return Err(ReaderError::ModuleNotFound(self.module.clone()))
+    };
+    let address = self.scan_signature(source, base_address).unwrap_or(base_address + first);
+    self.follow(source, address, rest)
+  }
+
+  /// Reads the name of the logged-in character, if the profile says where to find it.
+  pub fn read_character_name<S: MemorySource>(&self, source: &S) -> Option<String> {
+    opt!(self.character_name.as_ref()).read(source, self)
+  }
+
+  /// Follows `offsets` from `address`, reading a pointer and adding the next offset to it each
+  /// step.
+  fn follow<S: MemorySource>(&self,
+                             source: &S,
+                             mut address: usize,
+                             offsets: &[usize]) -> Result<usize, ReaderError> {
+    for offset in offsets {
+      let pointer = match self.pointer_width.read(source, address) {
+        Ok(p) => p,
+        // Permission or the process going away isn't the chain's fault
+        Err(e) => return Err(match e.kind() {
+          io::ErrorKind::PermissionDenied | io::ErrorKind::NotFound => {
+            ReaderError::from_read(e, address, self.pointer_width.size())
+          },
+          _ => ReaderError::InvalidPointerChain
+        })
+      };
+      if pointer == 0 {
+        return Err(ReaderError::InvalidPointerChain);
+      }
+      address = pointer + offset;
+    }
+    Ok(address)
+  }
+
+  fn scan_signature<S: MemorySource>(&self, source: &S, base_address: usize) -> Option<usize> {
+    let sig = opt!(self.signature.as_ref());
+    let image = opt!(signature::read_module_image(source, base_address));
+    sig.scan(&image, base_address)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use memory::InMemorySource;
+
+  #[test]
+  fn reads_profiles_from_json() {
+    let json = r#"{
+      "module": "ffxiv_dx11.exe",
+      "pointer_chain": [24789304, 96, 696],
+      "line_count_offset": 0,
+      "index_pointer_offset": 40,
+      "chat_pointer_offset": 52
+    }"#;
+    assert_eq!(OffsetProfile::from_reader(json.as_bytes()).unwrap(), OffsetProfile::default());
+  }
+
+  #[test]
+  fn rejects_incomplete_profiles() {
+    let json = r#"{ "module": "ffxiv_dx11.exe", "pointer_chain": [24789304, 96, 696] }"#;
+    let error = OffsetProfile::from_reader(json.as_bytes()).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+  }
+
+  #[test]
+  fn decodes_widths() {
+    let bytes = [0x78, 0x56, 0x34, 0x12, 0x01, 0, 0, 0];
+    assert_eq!(Width::U32.decode(&bytes), 0x1234_5678);
+    assert_eq!(Width::U64.decode(&bytes), 0x1_1234_5678);
+    assert_eq!(Width::default(), Width::U32);
+    let json = r#"{
+      "module": "ffxiv_dx11.exe",
+      "pointer_chain": [24789304, 96, 696],
+      "line_count_offset": 0,
+      "index_pointer_offset": 40,
+      "chat_pointer_offset": 52,
+      "pointer_width": "u64"
+    }"#;
+    let profile = OffsetProfile::from_reader(json.as_bytes()).unwrap();
+    assert_eq!((profile.pointer_width, profile.index_width), (Width::U64, Width::U32));
+  }
+
+  #[test]
+  fn follows_64_bit_pointers() {
+    let source = InMemorySource::new();
+    source.set_module("ffxiv_dx11.exe", 0x1000);
+    source.write(0x1010, &[0, 0x02, 0, 0, 0x01, 0, 0, 0]);
+    let profile = OffsetProfile {
+      pointer_chain: vec![0x10, 0x20],
+      pointer_width: Width::U64,
+      ..OffsetProfile::default()
+    };
+    assert_eq!(profile.resolve(&source).ok(), Some(0x1_0000_0220));
+    // The high half of the pointer is lost when it is read as 32 bits
+    let profile = OffsetProfile { pointer_width: Width::U32, ..profile };
+    assert_eq!(profile.resolve(&source).ok(), Some(0x220));
+  }
+}
@@ -0,0 +1,206 @@
+//! Offset profiles for several versions of the game
+//!
+//! The offsets of the chat log change with most game patches. A registry holds a profile for each
+//! known version of the game, keyed by a hash that identifies the build of the game's executable,
+//! so a reader can pick the right profile itself and refuse to read a version it doesn't know.
+//!
+//! Readers for a running game use the [`builtin`](struct.ProfileRegistry.html#method.builtin)
+//! registry unless told otherwise, so they fail with `UnknownGameVersion` on a build of the game
+//! the default profile hasn't been checked against. Until a build is added to
+//! `DEFAULT_PROFILE_VERSIONS` there is no built-in registry, and they read any build with the
+//! default profile.
+//!
+//! Registries can be loaded from JSON files. Hashes are written as 16 hexadecimal digits, as
+//! printed by `print_game_version`.
+//!
+//! ```json
+//! {
+//!   "versions": [
+//!     {
+//!       "name": "2017.06.06.0000.0000",
+//!       "hash": "3d2c5ab1e0f49a77",
+//!       "profile": {
+//!         "module": "ffxiv_dx11.exe",
+//!         "pointer_chain": [24789304, 96, 696],
+//!         "line_count_offset": 0,
+//!         "index_pointer_offset": 40,
+//!         "chat_pointer_offset": 52
+//!       }
+//!     }
+//!   ]
+//! }
+//! ```
+
+use serde_json;
+
+use error::ReaderError;
+use fnv;
+use memory::MemorySource;
+use process::GAME_PROCESS_NAME;
+use profile::OffsetProfile;
+use signature;
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// The builds of the game that the default offset profile has been checked against, as pairs of a
+/// name and a version hash.
+///
+/// These make up the [`builtin`](struct.ProfileRegistry.html#method.builtin) registry. A build is
+/// added with the hash `print_game_version` prints for it, once the default profile has been seen
+/// to read it correctly. No build has been added yet.
+pub const DEFAULT_PROFILE_VERSIONS: &[(&str, &str)] = &[];
+
+/// A set of offset profiles for different versions of the game.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProfileRegistry {
+  /// The name of the module whose headers identify the game version.
+  #[serde(default = "default_module")]
+  pub module: String,
+  /// The known versions of the game.
+  pub versions: Vec<VersionProfile>
+}
+
+fn default_module() -> String {
+  String::from(GAME_PROCESS_NAME)
+}
+
+/// The offset profile for one version of the game.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VersionProfile {
+  /// A name for the version, such as the game's version string.
+  pub name: String,
+  /// The version hash of the game's executable, as 16 hexadecimal digits.
+  ///
+  /// See [`game_version`](fn.game_version.html).
+  pub hash: String,
+  /// The profile to read this version with.
+  pub profile: OffsetProfile
+}
+
+impl ProfileRegistry {
+  /// The registry readers for a running game use by default.
+  ///
+  /// It holds the default offset profile for each build in `DEFAULT_PROFILE_VERSIONS`, so a reader
+  /// using it refuses to read any other build instead of reading it with offsets that are likely
+  /// wrong. This is `None` if there are no builds in `DEFAULT_PROFILE_VERSIONS`, as a registry
+  /// without them would refuse to read every build.
+  pub fn builtin() -> Option<Self> {
+    if DEFAULT_PROFILE_VERSIONS.is_empty() {
+      return None;
+    }
+    let versions = DEFAULT_PROFILE_VERSIONS.iter()
+      .map(|&(name, hash)| VersionProfile {
+        name: name.to_string(),
+        hash: hash.to_string(),
+        profile: OffsetProfile::default()
+      })
+      .collect();
+    Some(ProfileRegistry {
+      module: default_module(),
+      versions
+    })
+  }
+
+  /// Reads a registry from JSON.
+  pub fn from_reader<R: Read>(reader: R) -> io::Result<Self> {
+    serde_json::from_reader(reader).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+  }
+
+  /// Reads a registry from a JSON file.
+  pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+    ProfileRegistry::from_reader(File::open(path)?)
+  }
+
+  /// Finds the version with the version hash `hash`.
+  pub fn find(&self, hash: u64) -> Option<&VersionProfile> {
+    self.versions.iter().find(|v| u64::from_str_radix(&v.hash, 16).ok() == Some(hash))
+  }
+
+  /// Detects the version of the game in `source` and returns its profile.
+  ///
+  /// Fails with `UnknownGameVersion` if the registry has no profile for the version.
+  pub fn select<S: MemorySource>(&self, source: &S) -> Result<&OffsetProfile, ReaderError> {
+    let hash = game_version(source, &self.module)?;
+    match self.find(hash) {
+      Some(version) => Ok(&version.profile),
+      None => Err(ReaderError::UnknownGameVersion(hash))
+    }
+  }
+}
+
+/// Computes the version hash of the module named `module` in `source`.
+///
+/// The hash covers the parts of the module's PE headers that identify its build, including the
+/// time it was linked, and uses 64-bit FNV-1a. Every patch of the game
+/// gets a new hash, while the hash of one build stays the same across runs.
+///
+/// Fails with `ModuleNotFound` if the module is not loaded, and with an `Io` error of kind
+/// `InvalidData` if its headers can't be read.
+pub fn game_version<S: MemorySource>(source: &S, module: &str) -> Result<u64, ReaderError> {
+  let base_address = match source.base_address(module) {
+    Some(b) => b,
+    None => return Err(ReaderError::ModuleNotFound(module.to_string()))
+  };
+  match signature::read_module_identity(source, base_address) {
+    Some(identity) => Ok(fnv::hash(&identity)),
+    None => {
+      let message = format!("could not read the PE headers of {}", module);
+      Err(ReaderError::Io(io::Error::new(io::ErrorKind::InvalidData, message)))
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use memory::InMemorySource;
+
+  use byteorder::{ByteOrder, LittleEndian};
+
+  const BASE: usize = 0x1000_0000;
+
+  /// Creates a source with the PE headers of a build of the game linked at `timestamp`.
+  fn game(timestamp: u32) -> InMemorySource {
+    let mut headers = vec![0; 0x100];
+    headers[..2].copy_from_slice(b"MZ");
+    LittleEndian::write_u32(&mut headers[0x3c..], 0x80);
+    headers[0x80..0x84].copy_from_slice(b"PE\0\0");
+    LittleEndian::write_u32(&mut headers[0x88..], timestamp);
+    let source = InMemorySource::new();
+    source.set_module(GAME_PROCESS_NAME, BASE);
+    source.write(BASE, &headers);
+    source
+  }
+
+  #[test]
+  fn identifies_builds() {
+    let hash = game_version(&game(1), GAME_PROCESS_NAME).unwrap();
+    assert_eq!(game_version(&game(1), GAME_PROCESS_NAME).unwrap(), hash);
+    assert!(game_version(&game(2), GAME_PROCESS_NAME).unwrap() != hash);
+    match game_version(&game(1), "other.exe") {
+      Err(ReaderError::ModuleNotFound(ref m)) if m == "other.exe" => {},
+      r => panic!("expected a missing module, got {:?}", r)
+    }
+  }
+
+  #[test]
+  fn rejects_unknown_builds() {
+    let source = game(1);
+    let hash = game_version(&source, GAME_PROCESS_NAME).unwrap();
+    let mut registry = ProfileRegistry { module: default_module(), versions: Vec::new() };
+    match registry.select(&source) {
+      Err(ReaderError::UnknownGameVersion(h)) => assert_eq!(h, hash),
+      r => panic!("expected an unknown version, got {:?}", r)
+    }
+    let profile = OffsetProfile { line_count_offset: 8, ..Default::default() };
+    registry.versions.push(VersionProfile {
+      name: "test".into(),
+      hash: format!("{:016x}", hash),
+      profile: profile.clone()
+    });
+    assert_eq!(registry.select(&source).unwrap(), &profile);
+    assert!(registry.select(&game(2)).is_err());
+  }
+}
@@ -0,0 +1,253 @@
+//! Byte pattern scanning
+
+use byteorder::{ByteOrder, LittleEndian};
+
+use memory::MemorySource;
+
+/// A byte pattern that may contain wildcards.
+///
+/// Patterns are written as hexadecimal bytes separated by whitespace, with `?` or `??` matching any
+/// byte, e.g. `48 8B 05 ?? ?? ?? ?? 48 85 C0`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signature {
+  bytes: Vec<Option<u8>>
+}
+
+impl Signature {
+  /// Parses a pattern.
+  ///
+  /// Returns `None` if the pattern is empty, contains something other than bytes and wildcards, or
+  /// only contains wildcards.
+  pub fn parse(pattern: &str) -> Option<Signature> {
+    let mut bytes = Vec::new();
+    for token in pattern.split_whitespace() {
+      if token == "?" || token == "??" {
+        bytes.push(None);
+      } else if token.len() == 2 {
+        bytes.push(Some(opt!(u8::from_str_radix(token, 16).ok())));
+      } else {
+        return None;
+      }
+    }
+    if bytes.iter().all(Option::is_none) {
+      return None;
+    }
+    Some(Signature { bytes })
+  }
+
+  /// The number of bytes the pattern matches.
+  pub fn len(&self) -> usize {
+    self.bytes.len()
+  }
+
+  /// Checks if the pattern is empty, which is never the case for a parsed pattern.
+  pub fn is_empty(&self) -> bool {
+    self.bytes.is_empty()
+  }
+
+  /// Checks if the pattern matches the start of `bytes`.
+  pub fn matches(&self, bytes: &[u8]) -> bool {
+    bytes.len() >= self.bytes.len()
+      && self.bytes.iter().zip(bytes).all(|(p, b)| p.map(|p| p == *b).unwrap_or(true))
+  }
+
+  /// Finds the offset of the first match in `haystack`.
+  pub fn find(&self, haystack: &[u8]) -> Option<usize> {
+    self.find_from(haystack, 0)
+  }
+
+  /// Finds the offsets of all matches in `haystack`.
+  pub fn find_all(&self, haystack: &[u8]) -> Vec<usize> {
+    let mut found = Vec::new();
+    let mut start = 0;
+    while let Some(offset) = self.find_from(haystack, start) {
+      found.push(offset);
+      start = offset + 1;
+    }
+    found
+  }
+
+  fn find_from(&self, haystack: &[u8], start: usize) -> Option<usize> {
+    if haystack.len() < self.bytes.len() {
+      return None;
+    }
+    // Anchor on the first concrete byte so most positions are rejected with one comparison.
+    let (anchor_offset, anchor) = self.bytes.iter()
+      .enumerate()
+      .filter_map(|(i, b)| b.map(|b| (i, b)))
+      .next()
+      .unwrap();
+    let last = haystack.len() - self.bytes.len();
+    let mut i = start;
+    while i <= last {
+      let window = &haystack[i + anchor_offset..last + anchor_offset + 1];
+      i += opt!(window.iter().position(|b| *b == anchor));
+      if self.matches(&haystack[i..]) {
+        return Some(i);
+      }
+      i += 1;
+    }
+    None
+  }
+}
+
+/// Resolves the target of a RIP-relative reference in `image`.
+///
+/// `image` is the module image loaded at `image_base`. The signed 32-bit displacement is read at
+/// `displacement_offset`, and is relative to `instruction_end`, the offset of the end of the
+/// instruction containing it.
+pub fn rip_relative(image: &[u8],
+                    image_base: usize,
+                    displacement_offset: usize,
+                    instruction_end: usize) -> Option<usize> {
+  if displacement_offset + 4 > image.len() {
+    return None;
+  }
+  let displacement = LittleEndian::read_i32(&image[displacement_offset..displacement_offset + 4]);
+  let target = (image_base + instruction_end) as i64 + displacement as i64;
+  if target < 0 {
+    return None;
+  }
+  Some(target as usize)
+}
+
+/// Reads the parts of the PE headers of the module loaded at `base_address` that identify its
+/// build: the COFF file header, followed by the image size and checksum from the optional header.
+///
+/// Unlike the rest of the headers, these aren't changed by the loader. Returns `None` if the module
+/// can't be read or isn't a PE image.
+pub fn read_module_identity<S: MemorySource>(source: &S,
+                                             base_address: usize) -> Option<Vec<u8>> {
+  let pe_header = opt!(read_pe_header(source, base_address));
+  let mut identity = pe_header[0x04..0x18].to_vec();
+  identity.extend_from_slice(&pe_header[0x50..0x54]);
+  identity.extend_from_slice(&pe_header[0x58..0x5c]);
+  Some(identity)
+}
+
+/// Reads the size of the image of the PE module loaded at `base_address` from its optional header.
+pub fn module_image_size<S: MemorySource>(source: &S, base_address: usize) -> Option<usize> {
+  let pe_header = opt!(read_pe_header(source, base_address));
+  Some(LittleEndian::read_u32(&pe_header[0x50..]) as usize)
+}
+
+/// Reads the PE signature, the COFF file header and the start of the optional header of the module
+/// loaded at `base_address`, up to and including its checksum.
+fn read_pe_header<S: MemorySource>(source: &S, base_address: usize) -> Option<Vec<u8>> {
+  let dos_header = opt!(source.read(base_address, 0x40).ok());
+  if &dos_header[..2] != b"MZ" {
+    return None;
+  }
+  let pe_offset = LittleEndian::read_u32(&dos_header[0x3c..]) as usize;
+  let pe_header = opt!(source.read(opt!(base_address.checked_add(pe_offset)), 0x5c).ok());
+  if &pe_header[..4] != b"PE\0\0" {
+    return None;
+  }
+  Some(pe_header)
+}
+
+/// The largest module image `read_module_image` will read.
+///
+/// The game's own image is a fraction of this, so a larger size means the headers are corrupt or
+/// belong to something else.
+pub const MAX_IMAGE_SIZE: usize = 0x1000_0000;
+
+/// Reads the image of the PE module loaded at `base_address`.
+///
+/// The size of the image is taken from the PE optional header. Pages that can't be read are
+/// filled with zeroes. Returns `None` if the size is zero or larger than `MAX_IMAGE_SIZE`.
+pub fn read_module_image<S: MemorySource>(source: &S, base_address: usize) -> Option<Vec<u8>> {
+  const CHUNK_SIZE: usize = 0x100000;
+  const PAGE_SIZE: usize = 0x1000;
+
+  let size = opt!(module_image_size(source, base_address));
+  if size == 0 || size > MAX_IMAGE_SIZE {
+    return None;
+  }
+  let mut image = vec![0; size];
+  for (i, chunk) in image.chunks_mut(CHUNK_SIZE).enumerate() {
+    let chunk_address = base_address + i * CHUNK_SIZE;
+    if source.read_into(chunk_address, chunk).is_ok() {
+      continue;
+    }
+    for (j, page) in chunk.chunks_mut(PAGE_SIZE).enumerate() {
+      if source.read_into(chunk_address + j * PAGE_SIZE, page).is_err() {
+        for byte in page.iter_mut() {
+          *byte = 0;
+        }
+      }
+    }
+  }
+  Some(image)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use memory::InMemorySource;
+
+  const BASE: usize = 0x1000_0000;
+
+  /// Builds a module image of `size` bytes with just enough of the PE headers to be read.
+  fn image(size: usize) -> Vec<u8> {
+    let mut image = vec![0; size];
+    image[..2].copy_from_slice(b"MZ");
+    LittleEndian::write_u32(&mut image[0x3c..], 0x80);
+    image[0x80..0x84].copy_from_slice(b"PE\0\0");
+    LittleEndian::write_u32(&mut image[0x80 + 0x50..], size as u32);
+    image
+  }
+
+  #[test]
+  fn finds_from_offset() {
+    let sig = Signature::parse("?? 8B ?? C0").unwrap();
+    let haystack = [0x48, 0x8b, 0x11, 0xc0, 0x48, 0x8b, 0x22, 0xc0, 0x8b];
+    assert_eq!(sig.find_from(&haystack, 0), Some(0));
+    assert_eq!(sig.find_from(&haystack, 1), Some(4));
+    assert_eq!(sig.find_from(&haystack, 5), None);
+    assert_eq!(sig.find_from(&haystack, 100), None);
+    assert_eq!(sig.find_from(&haystack[..3], 0), None);
+    assert_eq!(sig.find_all(&haystack), vec![0, 4]);
+  }
+
+  #[test]
+  fn resolves_rip_relative_references() {
+    let mut image = image(0x2000);
+    // mov rax, [rip + disp32] at 0x1000, referencing 0x1800 and then 0x800
+    image[0x1000..0x1003].copy_from_slice(&[0x48, 0x8b, 0x05]);
+    LittleEndian::write_i32(&mut image[0x1003..], 0x1800 - 0x1007);
+    assert_eq!(rip_relative(&image, BASE, 0x1003, 0x1007), Some(BASE + 0x1800));
+    LittleEndian::write_i32(&mut image[0x1003..], 0x800 - 0x1007);
+    assert_eq!(rip_relative(&image, BASE, 0x1003, 0x1007), Some(BASE + 0x800));
+    LittleEndian::write_i32(&mut image[0x1003..], -0x2000);
+    assert_eq!(rip_relative(&image, 0, 0x1003, 0x1007), None);
+    assert_eq!(rip_relative(&image, BASE, 0x1ffd, 0x2001), None);
+  }
+
+  #[test]
+  fn reads_module_image() {
+    let source = InMemorySource::new();
+    let mut image = image(0x3000);
+    image[0x2000] = 0xaa;
+    // Leave the middle page unreadable.
+    source.write(BASE, &image[..0x1000]);
+    source.write(BASE + 0x2000, &image[0x2000..]);
+    let read = read_module_image(&source, BASE).unwrap();
+    assert_eq!(read.len(), 0x3000);
+    assert_eq!(&read[..0x1000], &image[..0x1000]);
+    assert!(read[0x1000..0x2000].iter().all(|b| *b == 0));
+    assert_eq!(read[0x2000], 0xaa);
+    let sig = Signature::parse("AA").unwrap();
+    assert_eq!(sig.find(&read), Some(0x2000));
+  }
+
+  #[test]
+  fn rejects_oversized_images() {
+    let source = InMemorySource::new();
+    let mut image = image(0x1000);
+    LittleEndian::write_u32(&mut image[0x80 + 0x50..], 0xffff_f000);
+    source.write(BASE, &image);
+    assert_eq!(module_image_size(&source, BASE), Some(0xffff_f000));
+    assert!(read_module_image(&source, BASE).is_none());
+  }
+}
@@ -0,0 +1,408 @@
+//! Capturing and replaying the chat log's memory
+//!
+//! A snapshot file records the memory making up the chat log over time, so problems can be
+//! reproduced without the game running. Each frame holds every region of memory read while
+//! capturing it: the pointer chain, the line count, the index table and the chat buffer.
+//!
+//! The file starts with a header, followed by any number of frames. All integers are
+//! little-endian.
+//!
+//! ```text
+//! header:  magic "FFXIVSNP", version (u32), profile length (u32), profile (JSON),
+//!          module base address (u64)
+//! frame:   timestamp in milliseconds (u64), chat log address (u64), chat buffer address (u64),
+//!          region count (u32), regions
+//! region:  address (u64), length (u32), bytes
+//! ```
+
+use byteorder::{ReadBytesExt, WriteBytesExt, LittleEndian};
+use serde_json;
+
+use memory::MemorySource;
+use profile::{OffsetProfile, Width};
+use chat_log::LINES_PER_PAGE;
+use signature;
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{self, Read, Write, BufReader};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MAGIC: &'static [u8] = b"FFXIVSNP";
+const VERSION: u32 = 1;
+/// The longest profile a snapshot is read with, well beyond any real profile's JSON.
+const MAX_PROFILE_LENGTH: usize = 1024 * 1024;
+/// The longest region a snapshot is read with. The largest region is the chat buffer, which is far
+/// smaller.
+const MAX_REGION_LENGTH: usize = 64 * 1024 * 1024;
+
+/// One capture of the chat log's memory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+  /// When the frame was captured, in milliseconds since the Unix epoch.
+  pub timestamp: u64,
+  /// The address of the chat log structure.
+  pub chat_log_address: usize,
+  /// The address of the chat buffer.
+  pub chat_address: usize,
+  /// The regions of memory read, as addresses and the bytes at them.
+  pub regions: Vec<(usize, Vec<u8>)>
+}
+
+impl Frame {
+  /// Checks if this frame captured the same memory as `other`, ignoring when they were captured.
+  pub fn same_memory(&self, other: &Frame) -> bool {
+    self.chat_log_address == other.chat_log_address
+      && self.chat_address == other.chat_address
+      && self.regions == other.regions
+  }
+
+  fn read_into(&self, address: usize, buf: &mut [u8]) -> bool {
+    for &(start, ref data) in &self.regions {
+      if address >= start && address + buf.len() <= start + data.len() {
+        buf.copy_from_slice(&data[address - start..address - start + buf.len()]);
+        return true;
+      }
+    }
+    false
+  }
+}
+
+/// A memory source that records every read made through it.
+struct Recorder<'a, S: 'a> {
+  source: &'a S,
+  regions: RefCell<Vec<(usize, Vec<u8>)>>
+}
+
+impl<'a, S: MemorySource> MemorySource for Recorder<'a, S> {
+  fn read_into(&self, address: usize, buf: &mut [u8]) -> io::Result<()> {
+    self.source.read_into(address, buf)?;
+    self.regions.borrow_mut().push((address, buf.to_vec()));
+    Ok(())
+  }
+
+  fn base_address(&self, module: &str) -> Option<usize> {
+    self.source.base_address(module)
+  }
+}
+
+/// Prepares a profile for capturing from `source`.
+///
+/// If the profile has a signature, it is resolved once, and the first offset of the chain is
+/// replaced with the address it found, so the module image doesn't have to be captured.
+///
+/// Returns the profile and the base address of its module.
+pub fn capture_profile<S: MemorySource>(source: &S,
+                                        profile: &OffsetProfile) -> Option<(OffsetProfile, usize)> {
+  let base_address = opt!(source.base_address(&profile.module));
+  let mut profile = profile.clone();
+  if let Some(sig) = profile.signature.take() {
+    let image = signature::read_module_image(source, base_address);
+    let found = image.and_then(|i| sig.scan(&i, base_address)).filter(|a| *a >= base_address);
+    if let (Some(address), Some(first)) = (found, profile.pointer_chain.first_mut()) {
+      *first = address - base_address;
+    }
+  }
+  Some((profile, base_address))
+}
+
+/// Captures a frame of the chat log described by `profile` from `source`.
+///
+/// Returns `None` if any of the memory can't be read.
+pub fn capture_frame<S: MemorySource>(source: &S, profile: &OffsetProfile) -> Option<Frame> {
+  let recorder = Recorder {
+    source,
+    regions: RefCell::new(Vec::new())
+  };
+  let pointer_width = profile.pointer_width;
+  let index_width = profile.index_width;
+  let chat_log_address = opt!(profile.resolve(&recorder).ok());
+  let chat_pointer = chat_log_address + profile.chat_pointer_offset;
+  let index_pointer = chat_log_address + profile.index_pointer_offset;
+  let lines = chat_log_address + profile.line_count_offset;
+  let chat_address = opt!(pointer_width.read(&recorder, chat_pointer).ok());
+  let pointer = opt!(pointer_width.read(&recorder, index_pointer).ok());
+  let count = opt!(Width::U32.read(&recorder, lines).ok());
+  let page_lines = count % LINES_PER_PAGE as usize;
+  if page_lines > 0 {
+    let table_size = index_width.size() * page_lines;
+    let table_address = opt!(pointer.checked_sub(table_size));
+    let table = opt!(recorder.read(table_address, table_size).ok());
+    let chat_size = index_width.decode(&table[table_size - index_width.size()..]);
+    opt!(recorder.read(chat_address, chat_size).ok());
+  }
+  let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+  Some(Frame {
+    timestamp: timestamp.as_secs() * 1000 + timestamp.subsec_nanos() as u64 / 1_000_000,
+    chat_log_address,
+    chat_address,
+    regions: recorder.regions.into_inner()
+  })
+}
+
+/// Writes snapshot files.
+pub struct SnapshotWriter<W> {
+  writer: W
+}
+
+impl<W: Write> SnapshotWriter<W> {
+  /// Writes the header of a snapshot to `writer`.
+  ///
+  /// `profile` should be prepared with [`capture_profile`](fn.capture_profile.html).
+  pub fn new(mut writer: W, profile: &OffsetProfile, module_base: usize) -> io::Result<Self> {
+    let profile_json = serde_json::to_vec(profile)
+      .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    writer.write_all(MAGIC)?;
+    writer.write_u32::<LittleEndian>(VERSION)?;
+    writer.write_u32::<LittleEndian>(profile_json.len() as u32)?;
+    writer.write_all(&profile_json)?;
+    writer.write_u64::<LittleEndian>(module_base as u64)?;
+    writer.flush()?;
+    Ok(SnapshotWriter { writer })
+  }
+
+  /// Writes a frame.
+  pub fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
+    self.write_regions(frame.timestamp, frame.chat_log_address, frame.chat_address, &frame.regions)
+  }
+
+  /// Writes a frame made up of `regions`, without having to build a `Frame` for them.
+  pub fn write_regions(&mut self,
+                       timestamp: u64,
+                       chat_log_address: usize,
+                       chat_address: usize,
+                       regions: &[(usize, Vec<u8>)]) -> io::Result<()> {
+    self.writer.write_u64::<LittleEndian>(timestamp)?;
+    self.writer.write_u64::<LittleEndian>(chat_log_address as u64)?;
+    self.writer.write_u64::<LittleEndian>(chat_address as u64)?;
+    self.writer.write_u32::<LittleEndian>(regions.len() as u32)?;
+    for &(address, ref data) in regions {
+      self.writer.write_u64::<LittleEndian>(address as u64)?;
+      self.writer.write_u32::<LittleEndian>(data.len() as u32)?;
+      self.writer.write_all(data)?;
+    }
+    self.writer.flush()
+  }
+}
+
+/// A snapshot read from a file.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+  /// The profile the snapshot was captured with.
+  pub profile: OffsetProfile,
+  /// The base address of the profile's module.
+  pub module_base: usize,
+  /// The captured frames, in order.
+  pub frames: Vec<Frame>
+}
+
+impl Snapshot {
+  /// Reads a snapshot.
+  ///
+  /// A frame cut off at the end, as happens when reading a file that is still being written, is
+  /// ignored.
+  pub fn from_reader<R: Read>(mut reader: R) -> io::Result<Self> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_owned());
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic)?;
+    if magic != MAGIC {
+      return Err(invalid("not a snapshot file"));
+    }
+    if reader.read_u32::<LittleEndian>()? != VERSION {
+      return Err(invalid("unsupported snapshot version"));
+    }
+    let profile_length = reader.read_u32::<LittleEndian>()? as usize;
+    if profile_length > MAX_PROFILE_LENGTH {
+      return Err(invalid("profile too long"));
+    }
+    let mut profile_json = vec![0; profile_length];
+    reader.read_exact(&mut profile_json)?;
+    let profile = OffsetProfile::from_reader(&profile_json[..])?;
+    let module_base = reader.read_u64::<LittleEndian>()? as usize;
+    let mut frames = Vec::new();
+    loop {
+      match Snapshot::read_frame(&mut reader) {
+        Ok(f) => frames.push(f),
+        Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+        Err(e) => return Err(e)
+      }
+    }
+    Ok(Snapshot {
+      profile,
+      module_base,
+      frames
+    })
+  }
+
+  /// Reads a snapshot from a file.
+  pub fn from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+    Snapshot::from_reader(BufReader::new(File::open(path)?))
+  }
+
+  fn read_frame<R: Read>(reader: &mut R) -> io::Result<Frame> {
+    let timestamp = reader.read_u64::<LittleEndian>()?;
+    let chat_log_address = reader.read_u64::<LittleEndian>()? as usize;
+    let chat_address = reader.read_u64::<LittleEndian>()? as usize;
+    let region_count = reader.read_u32::<LittleEndian>()?;
+    let mut regions = Vec::new();
+    for _ in 0..region_count {
+      let address = reader.read_u64::<LittleEndian>()? as usize;
+      let length = reader.read_u32::<LittleEndian>()? as usize;
+      if length > MAX_REGION_LENGTH {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "region too long"));
+      }
+      let mut data = vec![0; length];
+      reader.read_exact(&mut data)?;
+      regions.push((address, data));
+    }
+    Ok(Frame {
+      timestamp,
+      chat_log_address,
+      chat_address,
+      regions
+    })
+  }
+}
+
+/// A memory source that replays a snapshot.
+///
+/// Reads are served from the current frame, starting with the first. Each poll of a
+/// [`MemoryEntryReader`](../struct.MemoryEntryReader.html) after the first moves to the next frame,
+/// staying on the last frame once it is reached. A reader that stops once it runs out of messages
+/// will replay every frame before stopping.
+///
+/// The reader should use the snapshot's profile.
+///
+/// ```rust,no_run
+/// # use ffxiv_reader::MemoryEntryReader;
+/// # use ffxiv_reader::snapshot::{Snapshot, SnapshotSource};
+/// let snapshot = Snapshot::from_file("chat.snapshot").unwrap();
+/// let profile = snapshot.profile.clone();
+/// let mut reader = MemoryEntryReader::with_source(SnapshotSource::new(snapshot), true);
+/// reader.profile = profile;
+/// ```
+pub struct SnapshotSource {
+  snapshot: Snapshot,
+  position: Mutex<Option<usize>>
+}
+
+impl SnapshotSource {
+  pub fn new(snapshot: Snapshot) -> Self {
+    SnapshotSource {
+      snapshot,
+      position: Mutex::new(None)
+    }
+  }
+
+  /// The snapshot being replayed.
+  pub fn snapshot(&self) -> &Snapshot {
+    &self.snapshot
+  }
+
+  /// The frame currently being replayed.
+  pub fn current_frame(&self) -> Option<&Frame> {
+    let position = self.position.lock().unwrap().unwrap_or(0);
+    self.snapshot.frames.get(position)
+  }
+}
+
+impl MemorySource for SnapshotSource {
+  fn read_into(&self, address: usize, buf: &mut [u8]) -> io::Result<()> {
+    if self.current_frame().map(|f| f.read_into(address, buf)).unwrap_or(false) {
+      Ok(())
+    } else {
+      Err(io::Error::new(io::ErrorKind::UnexpectedEof, format!("no memory at {:#x}", address)))
+    }
+  }
+
+  fn base_address(&self, module: &str) -> Option<usize> {
+    if module == self.snapshot.profile.module {
+      Some(self.snapshot.module_base)
+    } else {
+      None
+    }
+  }
+
+  fn next_poll(&self) {
+    let mut position = self.position.lock().unwrap();
+    let last = self.snapshot.frames.len().saturating_sub(1);
+    *position = Some(position.map(|p| (p + 1).min(last)).unwrap_or(0));
+  }
+
+  fn has_pending_changes(&self) -> bool {
+    let position = self.position.lock().unwrap().unwrap_or(0);
+    position + 1 < self.snapshot.frames.len()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn frame(timestamp: u64, data: &[u8]) -> Frame {
+    Frame {
+      timestamp,
+      chat_log_address: 0x1000,
+      chat_address: 0x2000,
+      regions: vec![(0x1000, vec![1, 2, 3, 4]), (0x2000, data.to_vec())]
+    }
+  }
+
+  fn snapshot_bytes(frames: &[Frame]) -> Vec<u8> {
+    let mut writer = SnapshotWriter::new(Vec::new(), &OffsetProfile::default(), 0x400).unwrap();
+    for frame in frames {
+      writer.write_frame(frame).unwrap();
+    }
+    writer.writer
+  }
+
+  #[test]
+  fn reads_written_snapshots() {
+    let frames = vec![frame(1, b"first"), frame(2, b"second")];
+    let bytes = snapshot_bytes(&frames);
+    let snapshot = Snapshot::from_reader(&bytes[..]).unwrap();
+    assert_eq!(snapshot.profile, OffsetProfile::default());
+    assert_eq!(snapshot.module_base, 0x400);
+    assert_eq!(snapshot.frames, frames);
+    // A frame still being written is ignored
+    let snapshot = Snapshot::from_reader(&bytes[..bytes.len() - 3]).unwrap();
+    assert_eq!(snapshot.frames, &frames[..1]);
+  }
+
+  #[test]
+  fn rejects_overlong_lengths() {
+    let mut bytes = MAGIC.to_vec();
+    bytes.write_u32::<LittleEndian>(VERSION).unwrap();
+    bytes.write_u32::<LittleEndian>(u32::max_value()).unwrap();
+    let e = Snapshot::from_reader(&bytes[..]).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    let mut bytes = snapshot_bytes(&[]);
+    bytes.write_u64::<LittleEndian>(1).unwrap();
+    bytes.write_u64::<LittleEndian>(0x1000).unwrap();
+    bytes.write_u64::<LittleEndian>(0x2000).unwrap();
+    bytes.write_u32::<LittleEndian>(1).unwrap();
+    bytes.write_u64::<LittleEndian>(0x1000).unwrap();
+    bytes.write_u32::<LittleEndian>(u32::max_value()).unwrap();
+    let e = Snapshot::from_reader(&bytes[..]).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+  }
+
+  #[test]
+  fn replays_a_frame_per_poll() {
+    let bytes = snapshot_bytes(&[frame(1, b"first"), frame(2, b"again")]);
+    let source = SnapshotSource::new(Snapshot::from_reader(&bytes[..]).unwrap());
+    assert_eq!(source.read(0x2000, 5).unwrap(), b"first");
+    source.next_poll();
+    assert_eq!(source.read(0x2000, 5).unwrap(), b"first");
+    assert!(source.has_pending_changes());
+    source.next_poll();
+    assert_eq!(source.read(0x2000, 5).unwrap(), b"again");
+    assert!(!source.has_pending_changes());
+    source.next_poll();
+    assert_eq!(source.read(0x2000, 5).unwrap(), b"again");
+    assert!(source.read(0x3000, 1).is_err());
+    assert_eq!(source.base_address(&OffsetProfile::default().module), Some(0x400));
+  }
+}
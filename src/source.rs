@@ -0,0 +1,116 @@
+//! A common interface for everything entries can be read from
+
+use error::ReaderError;
+use events::ReaderEvent;
+use memory::MemorySource;
+use messages::entries::Entry;
+use act::ActReader;
+use json::JsonEntryReader;
+use channel::EventReceiver;
+use MemoryEntryReader;
+use parse_entry;
+
+/// Something that entries can be read from.
+///
+/// This is implemented by [`MemoryEntryReader`](../struct.MemoryEntryReader.html),
+/// [`ActReader`](../act/struct.ActReader.html) and
+/// [`JsonEntryReader`](../json/struct.JsonEntryReader.html), so code that only needs entries can
+/// accept any of them.
+///
+/// ```rust,no_run
+/// # use ffxiv_reader::EntrySource;
+/// fn print_all<R: EntrySource>(reader: &R) {
+///   for entry in reader.entries() {
+///     println!("{:?}", entry);
+///   }
+/// }
+/// ```
+///
+/// To get the raw bytes of each entry from a `MemoryEntryReader` instead, use its `start_raw`
+/// method.
+pub trait EntrySource {
+  /// Starts reading in the background, sending each entry along with any status events.
+  ///
+  /// This will fail if the source has already been started and not stopped, or if it can't be
+  /// read.
+  fn start(&self) -> Result<EventReceiver<Entry>, ReaderError>;
+
+  /// Stops reading.
+  fn stop(&self);
+
+  /// Starts reading and iterates over the entries read.
+  fn entries(&self) -> Entries {
+    match self.start() {
+      Ok(rx) => Entries { rx: Some(rx), error: None },
+      Err(e) => Entries { rx: None, error: Some(e) }
+    }
+  }
+}
+
+/// The iterator for [`EntrySource::entries`](trait.EntrySource.html#method.entries).
+///
+/// Status events are skipped. The last error encountered, including any error from starting the
+/// source, can be checked with `error()`.
+pub struct Entries {
+  rx: Option<EventReceiver<Entry>>,
+  error: Option<ReaderError>
+}
+
+impl Entries {
+  /// Gets the last error encountered by the source, if any.
+  pub fn error(&self) -> Option<&ReaderError> {
+    self.error.as_ref()
+  }
+}
+
+impl Iterator for Entries {
+  type Item = Entry;
+
+  fn next(&mut self) -> Option<Entry> {
+    let rx = match self.rx {
+      Some(ref r) => r,
+      None => return None
+    };
+    loop {
+      match rx.recv() {
+        Ok(ReaderEvent::Item(entry)) => return Some(entry),
+        Ok(ReaderEvent::Error(e)) => self.error = Some(e),
+        Ok(_) => continue,
+        Err(_) => return None
+      }
+    }
+  }
+}
+
+impl<S> EntrySource for MemoryEntryReader<S>
+  where S: MemorySource + Send + Sync + 'static
+{
+  /// Starts the memory reading loop, skipping entries that can't be parsed.
+  fn start(&self) -> Result<EventReceiver<Entry>, ReaderError> {
+    self.spawn(|bytes, player| parse_entry(bytes, player).ok())
+  }
+
+  fn stop(&self) {
+    MemoryEntryReader::stop(self)
+  }
+}
+
+impl EntrySource for ActReader {
+  fn start(&self) -> Result<EventReceiver<Entry>, ReaderError> {
+    ActReader::start(self)
+  }
+
+  fn stop(&self) {
+    ActReader::stop(self)
+  }
+}
+
+impl EntrySource for JsonEntryReader {
+  fn start(&self) -> Result<EventReceiver<Entry>, ReaderError> {
+    JsonEntryReader::start(self)
+  }
+
+  fn stop(&self) {
+    JsonEntryReader::stop(self)
+  }
+}
@@ -0,0 +1,459 @@
+//! Readers as asynchronous streams
+//!
+//! This module is only available with the `stream` feature. It provides each reader as a
+//! [`futures::Stream`](https://docs.rs/futures/0.1/futures/stream/trait.Stream.html) of entries
+//! that does its waiting on tokio's timer instead of on a thread of its own. Nothing is read unless
+//! the stream is polled, and dropping the stream stops reading.
+//!
+//! The streams must be polled from within a tokio runtime.
+//!
+//! ```rust,no_run
+//! # extern crate ffxiv_reader;
+//! # extern crate futures;
+//! # extern crate tokio;
+//! # use ffxiv_reader::MemoryEntryReader;
+//! # use futures::{Future, Stream};
+//! # fn main() {
+//! # let my_pid = 0;
+//! let reader = MemoryEntryReader::new(my_pid, false);
+//! let stream = reader.stream().unwrap();
+//! tokio::run(stream.for_each(|entry| {
+//!   println!("{:?}", entry);
+//!   Ok(())
+//! }).map_err(|e| println!("{}", e)));
+//! # }
+//! ```
+
+use futures::{Async, Future, Poll, Stream};
+use tokio::fs::File;
+use tokio::fs::file::OpenFuture;
+use tokio::io::AsyncRead;
+use tokio::timer::Delay;
+
+use act::{self, ActReader};
+use checkpoint::{Checkpoint, MemoryCheckpoint, ActCheckpoint, ResumeFilter};
+use chat_log::ChatLog;
+use error::ReaderError;
+use events::ReaderEvent;
+use memory::MemorySource;
+use messages::entries::Entry;
+use player::LocalPlayer;
+use poll::PollTimer;
+use MemoryEntryReader;
+use parse_entry;
+
+use std::collections::VecDeque;
+use std::io::{self, SeekFrom};
+use std::mem;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// How long to wait before trying to reattach.
+const REATTACH_INTERVAL: Duration = Duration::from_secs(1);
+
+fn timer_error(e: ::tokio::timer::Error) -> ReaderError {
+  ReaderError::Io(io::Error::new(io::ErrorKind::Other, e))
+}
+
+impl<S: MemorySource> MemoryEntryReader<S> {
+  /// Creates a stream of the entries read by this reader.
+  ///
+  /// The stream behaves like the iterator returned by `iter`, but waits using tokio's timer. It is
+  /// independent of `start` and `stop`, and any number of streams can read from the same reader.
+  ///
+  /// This will fail if the memory can't be read, unless `reconnect` is set. Without `reconnect`,
+  /// the stream ends with an error if the memory can no longer be read.
+  pub fn stream(&self) -> Result<MemoryEntryStream<S>, ReaderError> {
+    let checkpoint = match self.checkpoint {
+      Some(ref path) => MemoryCheckpoint::load(path)?,
+      None => MemoryCheckpoint::default()
+    };
+    let registry = self.registry.clone();
+    let mut chat_log = ChatLog::new(self.source.clone(), self.profile.clone(), registry);
+    let mut delay = None;
+    if let Err(e) = chat_log.attach() {
+      if !self.reconnect {
+        return Err(e);
+      }
+      delay = Some(Delay::new(Instant::now() + REATTACH_INTERVAL));
+    }
+    Ok(MemoryEntryStream {
+      chat_log,
+      stop: self.stop,
+      reconnect: self.reconnect,
+      checkpoint_path: self.checkpoint.clone(),
+      resume: checkpoint.resume(),
+      checkpoint,
+      pending: VecDeque::new(),
+      timer: self.poll.timer()?,
+      player: None,
+      delay
+    })
+  }
+}
+
+/// A stream of the entries read by a
+/// [`MemoryEntryReader`](../struct.MemoryEntryReader.html).
+///
+/// Created by [`MemoryEntryReader::stream`](../struct.MemoryEntryReader.html#method.stream).
+/// Entries that can't be parsed are skipped.
+///
+/// A stream only yields entries, so it can't report the status events sent by the reader's own
+/// loop. Lines overwritten before they could be read are skipped without a `Gap`, and with
+/// `reconnect`, losing and regaining the memory and the errors that caused it aren't reported.
+/// Use [`start_raw`](../struct.MemoryEntryReader.html#method.start_raw) or
+/// [`EntrySource::start`](../source/trait.EntrySource.html#tymethod.start) to see them.
+///
+/// Reading memory, reattaching and saving the checkpoint are done on the thread polling the stream,
+/// without going through tokio. Reads are small and checkpoints are single small files, but
+/// reattaching to a process found by name scans `/proc` once a second while detached, which can
+/// take a few milliseconds on systems with many processes.
+pub struct MemoryEntryStream<S> {
+  chat_log: ChatLog<S>,
+  stop: bool,
+  reconnect: bool,
+  checkpoint_path: Option<PathBuf>,
+  checkpoint: MemoryCheckpoint,
+  resume: ResumeFilter,
+  pending: VecDeque<Entry>,
+  timer: PollTimer,
+  player: Option<LocalPlayer>,
+  delay: Option<Delay>
+}
+
+impl<S: MemorySource> Stream for MemoryEntryStream<S> {
+  type Item = Entry;
+  type Error = ReaderError;
+
+  fn poll(&mut self) -> Poll<Option<Entry>, ReaderError> {
+    loop {
+      if let Some(entry) = self.pending.pop_front() {
+        return Ok(Async::Ready(Some(entry)));
+      }
+      if let Some(mut delay) = self.delay.take() {
+        if delay.poll().map_err(timer_error)?.is_not_ready() {
+          self.delay = Some(delay);
+          return Ok(Async::NotReady);
+        }
+      }
+      // Wait for the pointers to become valid again if reading failed
+      if !self.chat_log.is_attached() {
+        if !(self.chat_log.source().reattach() && self.chat_log.attach().is_ok()) {
+          self.delay = Some(Delay::new(Instant::now() + REATTACH_INTERVAL));
+          continue;
+        }
+        // The character may have changed while detached
+        self.player = None;
+      }
+      let events = match self.chat_log.poll() {
+        Ok(e) => e,
+        Err(_) if self.reconnect => {
+          // The chat log detached itself, so wait before trying to reattach
+          self.delay = Some(Delay::new(Instant::now() + REATTACH_INTERVAL));
+          continue;
+        },
+        Err(e) => return Err(e)
+      };
+      // If there are no new messages, wait and check again
+      if events.is_empty() {
+        if self.chat_log.source().has_pending_changes() {
+          continue;
+        } else if self.stop {
+          return Ok(Async::Ready(None));
+        }
+        self.delay = Some(Delay::new(Instant::now() + self.timer.idle()));
+        continue;
+      }
+      self.timer.found();
+      if self.player.is_none() {
+        self.player = LocalPlayer::read(self.chat_log.source(), self.chat_log.profile());
+      }
+      // Queue each new message, skipping any delivered before the checkpoint
+      let mut delivered = false;
+      for bytes in events.into_iter().filter_map(ReaderEvent::item) {
+        if !self.resume.is_new(&bytes) {
+          continue;
+        }
+        self.checkpoint.record(&bytes);
+        delivered = true;
+        if let Ok(entry) = parse_entry(bytes, self.player.as_ref()) {
+          self.pending.push_back(entry);
+        }
+      }
+      if let (true, Some(path)) = (delivered, self.checkpoint_path.as_ref()) {
+        Checkpoint::Memory(self.checkpoint.clone()).save(path)?;
+      }
+    }
+  }
+}
+
+impl ActReader {
+  /// Creates a stream of the entries in this reader's log file.
+  ///
+  /// The file is opened and read using tokio's asynchronous file I/O. If `stop` is `false`, the
+  /// stream waits for more lines to be written once it reaches the end of the file.
+  ///
+  /// A reader following a directory streams the log it would start from, given by
+  /// [`start_position`](../act/struct.ActReader.html#method.start_position), without moving on to
+  /// newer logs. Lines are not checked against their hashes.
+  ///
+  /// This will fail if the reader's checkpoint can't be read, or if a directory has no logs in it.
+  pub fn stream(&self) -> Result<ActEntryStream, ReaderError> {
+    let (file, offset) = self.start_position()?;
+    Ok(ActEntryStream {
+      state: ActState::Opening(File::open(file.clone())),
+      file,
+      stop: self.stop,
+      checkpoint_path: self.checkpoint.clone(),
+      offset,
+      saved_offset: offset,
+      buffer: Vec::new(),
+      pending: VecDeque::new(),
+      timer: self.poll.timer()?
+    })
+  }
+}
+
+enum ActState {
+  Opening(OpenFuture<PathBuf>),
+  Seeking(File),
+  Reading(File),
+  Waiting(File, Delay),
+  Done
+}
+
+/// A stream of the entries in an ACT log file.
+///
+/// Created by [`ActReader::stream`](../act/struct.ActReader.html#method.stream).
+pub struct ActEntryStream {
+  state: ActState,
+  file: PathBuf,
+  stop: bool,
+  checkpoint_path: Option<PathBuf>,
+  /// The offset just past the last complete line read
+  offset: u64,
+  saved_offset: u64,
+  /// Bytes read after the last complete line
+  buffer: Vec<u8>,
+  pending: VecDeque<Entry>,
+  timer: PollTimer
+}
+
+impl ActEntryStream {
+  /// Parses any complete lines in the buffer.
+  fn parse_lines(&mut self) {
+    let end = match self.buffer.iter().rposition(|b| *b == b'\n') {
+      Some(i) => i + 1,
+      None => return
+    };
+    let rest = self.buffer.split_off(end);
+    let lines = mem::replace(&mut self.buffer, rest);
+    self.offset += lines.len() as u64;
+    let lines = String::from_utf8_lossy(&lines);
+    self.pending.extend(lines.lines().filter_map(act::parse_line));
+  }
+
+  /// Saves the position once caught up with the file.
+  fn save_checkpoint(&mut self) -> Result<(), ReaderError> {
+    if let (true, Some(path)) = (self.offset != self.saved_offset, self.checkpoint_path.as_ref()) {
+      self.saved_offset = self.offset;
+      let checkpoint = ActCheckpoint { offset: self.offset, file: Some(self.file.clone()) };
+      Checkpoint::Act(checkpoint).save(path)?;
+    }
+    Ok(())
+  }
+}
+
+impl Stream for ActEntryStream {
+  type Item = Entry;
+  type Error = ReaderError;
+
+  fn poll(&mut self) -> Poll<Option<Entry>, ReaderError> {
+    loop {
+      if let Some(entry) = self.pending.pop_front() {
+        return Ok(Async::Ready(Some(entry)));
+      }
+      self.state = match mem::replace(&mut self.state, ActState::Done) {
+        ActState::Opening(mut open) => match open.poll()? {
+          Async::Ready(file) => ActState::Seeking(file),
+          Async::NotReady => {
+            self.state = ActState::Opening(open);
+            return Ok(Async::NotReady);
+          }
+        },
+        ActState::Seeking(mut file) => {
+          let length = match file.poll_metadata()? {
+            Async::Ready(m) => m.len(),
+            Async::NotReady => {
+              self.state = ActState::Seeking(file);
+              return Ok(Async::NotReady);
+            }
+          };
+          // If the file is shorter than the checkpoint, it was replaced, so read it from the start
+          if self.offset > length {
+            self.offset = 0;
+          } else if file.poll_seek(SeekFrom::Start(self.offset))?.is_not_ready() {
+            self.state = ActState::Seeking(file);
+            return Ok(Async::NotReady);
+          }
+          ActState::Reading(file)
+        },
+        ActState::Reading(mut file) => {
+          let mut buf = [0; 8192];
+          match file.poll_read(&mut buf)? {
+            Async::Ready(0) if self.stop => {
+              // A line that was never finished isn't sent. The checkpoint is left before it, so it
+              // is read whole once it is.
+              self.buffer.clear();
+              self.save_checkpoint()?;
+              ActState::Done
+            },
+            Async::Ready(0) => {
+              let delay = Delay::new(Instant::now() + self.timer.idle());
+              self.state = ActState::Waiting(file, delay);
+              self.save_checkpoint()?;
+              continue;
+            },
+            Async::Ready(n) => {
+              self.timer.found();
+              self.buffer.extend_from_slice(&buf[..n]);
+              self.parse_lines();
+              ActState::Reading(file)
+            },
+            Async::NotReady => {
+              self.state = ActState::Reading(file);
+              return Ok(Async::NotReady);
+            }
+          }
+        },
+        ActState::Waiting(file, mut delay) => {
+          if delay.poll().map_err(timer_error)?.is_not_ready() {
+            self.state = ActState::Waiting(file, delay);
+            return Ok(Async::NotReady);
+          }
+          ActState::Reading(file)
+        },
+        ActState::Done => return Ok(Async::Ready(None))
+      };
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use messages::HasDisplayText;
+  use memory::InMemorySource;
+  use poll::PollInterval;
+  use testing;
+
+  use tokio::runtime::Runtime;
+
+  use std::env;
+  use std::fs::{self, OpenOptions};
+  use std::io::Write;
+  use std::path::Path;
+
+  fn messages(entries: Vec<Entry>) -> Vec<String> {
+    entries.iter().map(|e| e.message.display_text()).collect()
+  }
+
+  /// Waits for the next entry of `stream`, failing if the stream ends.
+  fn next<S>(rt: &mut Runtime, stream: S) -> (String, S)
+    where S: Stream<Item = Entry, Error = ReaderError> + Send + 'static
+  {
+    match rt.block_on(stream.into_future()) {
+      Ok((Some(entry), stream)) => (entry.message.display_text(), stream),
+      Ok((None, _)) => panic!("expected an entry, got the end of the stream"),
+      Err((e, _)) => panic!("expected an entry, got {:?}", e)
+    }
+  }
+
+  #[test]
+  fn streams_memory_until_stopped() {
+    let source = testing::game();
+    let lines = [testing::line(100, "A", "one"), testing::line(101, "B", "two")];
+    testing::write_lines(&source, &lines);
+    let reader = MemoryEntryReader::with_source(source, true);
+    let mut rt = Runtime::new().unwrap();
+    let entries = rt.block_on(reader.stream().unwrap().collect()).unwrap();
+    assert_eq!(messages(entries), vec!["one", "two"]);
+  }
+
+  #[test]
+  fn streams_new_memory_entries() {
+    let mut reader = MemoryEntryReader::with_source(testing::game(), false);
+    reader.poll = PollInterval::Fixed(Duration::from_millis(5));
+    let mut lines = vec![testing::line(100, "A", "one")];
+    testing::write_lines(reader.source(), &lines);
+    let mut rt = Runtime::new().unwrap();
+    let (first, stream) = next(&mut rt, reader.stream().unwrap());
+    assert_eq!(first, "one");
+    lines.push(testing::line(101, "B", "two"));
+    testing::write_lines(reader.source(), &lines);
+    let (second, _) = next(&mut rt, stream);
+    assert_eq!(second, "two");
+  }
+
+  #[test]
+  fn fails_without_memory_unless_reconnecting() {
+    let reader = MemoryEntryReader::with_source(InMemorySource::new(), true);
+    assert!(reader.stream().is_err());
+  }
+
+  /// Writes `content` to a file in the temporary directory, unique to the test named `name`.
+  fn write_log(name: &str, content: &str) -> PathBuf {
+    let name = format!("ffxiv_reader_stream_{}_{}.log", name, ::std::process::id());
+    let path = env::temp_dir().join(name);
+    fs::File::create(&path).unwrap().write_all(content.as_bytes()).unwrap();
+    path
+  }
+
+  fn append(path: &Path, content: &str) {
+    OpenOptions::new().append(true).open(path).unwrap().write_all(content.as_bytes()).unwrap();
+  }
+
+  /// Builds a chat line with the message `message`, without a line break.
+  fn chat_line(message: &str) -> String {
+    format!("00|2017-06-20T12:00:00.0000000+00:00|000a|Sender|{}|0123456789abcdef", message)
+  }
+
+  #[test]
+  fn skips_unfinished_act_lines_when_stopping() {
+    let path = write_log("stop", &format!("{}\n{}\n{}", chat_line("one"), chat_line("two"),
+                                          chat_line("three")));
+    let checkpoint = env::temp_dir()
+      .join(format!("ffxiv_reader_stream_stop_{}.checkpoint", ::std::process::id()));
+    let mut reader = ActReader::new(&path, true);
+    reader.checkpoint = Some(checkpoint.clone());
+    let mut rt = Runtime::new().unwrap();
+    let entries = rt.block_on(reader.stream().unwrap().collect()).unwrap();
+    assert_eq!(messages(entries), vec!["one", "two"]);
+    append(&path, "\n");
+    let entries = rt.block_on(reader.stream().unwrap().collect()).unwrap();
+    assert_eq!(messages(entries), vec!["three"]);
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&checkpoint).unwrap();
+  }
+
+  #[test]
+  fn waits_for_unfinished_act_lines() {
+    let path = write_log("follow", &format!("{}\n{}", chat_line("one"), &chat_line("two")[..20]));
+    let mut reader = ActReader::new(&path, false);
+    reader.poll = PollInterval::Fixed(Duration::from_millis(5));
+    let mut rt = Runtime::new().unwrap();
+    let (first, stream) = next(&mut rt, reader.stream().unwrap());
+    assert_eq!(first, "one");
+    append(&path, &format!("{}\n", &chat_line("two")[20..]));
+    let (second, _) = next(&mut rt, stream);
+    assert_eq!(second, "two");
+    fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn fails_to_open_missing_act_logs() {
+    let reader = ActReader::new(env::temp_dir().join("ffxiv_reader_stream_missing.log"), true);
+    let mut rt = Runtime::new().unwrap();
+    assert!(rt.block_on(reader.stream().unwrap().collect()).is_err());
+  }
+}
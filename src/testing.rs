@@ -0,0 +1,58 @@
+//! A fake game in memory for tests
+//!
+//! The game's chat log is where the default offset profile looks for it, so readers can be tested
+//! without changing their profile.
+
+use memory::InMemorySource;
+
+use byteorder::{ByteOrder, LittleEndian};
+
+/// The base address of the game's module.
+pub const BASE: usize = 0x1000_0000;
+/// The address the first pointer of the default profile's chain leads to.
+const FIRST: usize = 0x2000_0000;
+/// The address the second pointer of the default profile's chain leads to.
+const SECOND: usize = 0x3000_0000;
+/// The address of the chat log structure.
+pub const CHAT_LOG: usize = SECOND + 0x2b8;
+const INDEX: usize = 0x4000_0000;
+const CHAT: usize = 0x5000_0000;
+
+fn u32_bytes(value: usize) -> Vec<u8> {
+  let mut bytes = vec![0; 4];
+  LittleEndian::write_u32(&mut bytes, value as u32);
+  bytes
+}
+
+/// Creates a source holding a game with an empty chat log.
+pub fn game() -> InMemorySource {
+  let source = InMemorySource::new();
+  source.set_module("ffxiv_dx11.exe", BASE);
+  source.write(BASE + 0x017a4138, &u32_bytes(FIRST));
+  source.write(FIRST + 0x60, &u32_bytes(SECOND));
+  write_lines(&source, &[]);
+  source
+}
+
+/// Builds the bytes of a say line sent by `sender` at `timestamp`.
+pub fn line(timestamp: u32, sender: &str, message: &str) -> Vec<u8> {
+  let mut bytes = u32_bytes(timestamp as usize);
+  bytes.extend(&[0x0a, 0, 0, 0]);
+  bytes.extend(format!(":{}:{}", sender, message).as_bytes());
+  bytes
+}
+
+/// Writes `lines` as every line written to the chat log so far.
+pub fn write_lines(source: &InMemorySource, lines: &[Vec<u8>]) {
+  let mut index = Vec::new();
+  let mut chat = Vec::new();
+  for line in lines {
+    chat.extend(line);
+    index.extend(u32_bytes(chat.len()));
+  }
+  source.write(INDEX, &index);
+  source.write(CHAT, &chat);
+  source.write(CHAT_LOG, &u32_bytes(lines.len()));
+  source.write(CHAT_LOG + 40, &u32_bytes(INDEX + index.len()));
+  source.write(CHAT_LOG + 52, &u32_bytes(CHAT));
+}